@@ -0,0 +1,277 @@
+// Retro sound effects synthesised into WAV buffers at startup, so the game
+// ships without any audio assets. Sound is only heard when macroquad's audio
+// backend is enabled (the `audio` feature on native builds, always on wasm);
+// otherwise every call here is silently a no-op.
+use macroquad::audio::{
+    PlaySoundParams, Sound, load_sound_from_bytes, play_sound, set_sound_volume, stop_sound,
+};
+
+use crate::rng::Rng;
+use crate::world::WorldEvent;
+
+const SAMPLE_RATE: u32 = 22050;
+const VOLUME_KEY: &str = "volume.txt";
+// Seconds between heartbeat notes with a single asteroid left, and the most
+// it slows down to on a crowded field.
+const BEAT_FASTEST: f32 = 0.25;
+const BEAT_SLOWEST: f32 = 1.0;
+const BEAT_PER_ASTEROID: f32 = 0.05;
+
+// Each level is 0 to 1; effects play at `master * sfx`, the heartbeat and
+// jingle at `master * music`.
+#[derive(Clone, Copy)]
+pub struct Volume {
+    pub master: f32,
+    pub sfx: f32,
+    pub music: f32,
+}
+
+impl Default for Volume {
+    fn default() -> Volume {
+        Volume {
+            master: 0.8,
+            sfx: 1.0,
+            music: 0.6,
+        }
+    }
+}
+
+impl Volume {
+    pub fn load() -> Volume {
+        let Some(text) = crate::storage::load(VOLUME_KEY) else {
+            return Volume::default();
+        };
+        let levels: Vec<f32> = text
+            .split_whitespace()
+            .filter_map(|v| v.parse().ok())
+            .collect();
+        match levels.as_slice() {
+            [master, sfx, music] => Volume {
+                master: master.clamp(0.0, 1.0),
+                sfx: sfx.clamp(0.0, 1.0),
+                music: music.clamp(0.0, 1.0),
+            },
+            _ => Volume::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let text = format!("{} {} {}\n", self.master, self.sfx, self.music);
+        crate::storage::save(VOLUME_KEY, &text)
+    }
+}
+
+pub struct Audio {
+    pub volume: Volume,
+    fire: Sound,
+    thrust: Sound,
+    // Large, medium and small asteroid breaking.
+    bangs: [Sound; 3],
+    explosion: Sound,
+    jingle: Sound,
+    pickup: Sound,
+    shield: Sound,
+    beats: [Sound; 2],
+    thrusting: bool,
+    next_beat: f32,
+    beat: usize,
+}
+
+impl Audio {
+    pub async fn load(volume: Volume) -> Audio {
+        let mut rng = Rng::new(1);
+        Audio {
+            volume,
+            fire: sound(&sweep(1200.0, 300.0, 0.12, 0.3)).await,
+            thrust: sound(&noise(&mut rng, 0.5, 0.08, 0.25, false)).await,
+            bangs: [
+                sound(&noise(&mut rng, 0.9, 0.05, 0.8, true)).await,
+                sound(&noise(&mut rng, 0.6, 0.1, 0.7, true)).await,
+                sound(&noise(&mut rng, 0.35, 0.2, 0.6, true)).await,
+            ],
+            explosion: sound(&mix(
+                &noise(&mut rng, 1.4, 0.04, 0.9, true),
+                &sweep(60.0, 30.0, 1.4, 0.5),
+            ))
+            .await,
+            jingle: sound(
+                &[523.25, 659.25, 783.99, 1046.5]
+                    .map(|f| sweep(f, f, 0.1, 0.3))
+                    .concat(),
+            )
+            .await,
+            pickup: sound(&sweep(400.0, 1600.0, 0.2, 0.3)).await,
+            shield: sound(&mix(
+                &sweep(220.0, 880.0, 0.25, 0.3),
+                &noise(&mut rng, 0.25, 0.3, 0.4, true),
+            ))
+            .await,
+            beats: [
+                sound(&sweep(110.0, 100.0, 0.09, 0.6)).await,
+                sound(&sweep(98.0, 90.0, 0.09, 0.6)).await,
+            ],
+            thrusting: false,
+            next_beat: 0.0,
+            beat: 0,
+        }
+    }
+
+    fn sfx(&self, sound: &Sound) {
+        let volume = self.volume.master * self.volume.sfx;
+        play_sound(
+            sound,
+            PlaySoundParams {
+                looped: false,
+                volume,
+            },
+        );
+    }
+
+    fn music(&self, sound: &Sound) {
+        let volume = self.volume.master * self.volume.music;
+        play_sound(
+            sound,
+            PlaySoundParams {
+                looped: false,
+                volume,
+            },
+        );
+    }
+
+    pub fn play(&self, event: &WorldEvent) {
+        match *event {
+            WorldEvent::Fire => self.sfx(&self.fire),
+            WorldEvent::AsteroidBroken { scale, .. } => {
+                let bang = if scale > 0.75 {
+                    0
+                } else if scale > 0.55 {
+                    1
+                } else {
+                    2
+                };
+                self.sfx(&self.bangs[bang]);
+            }
+            WorldEvent::SaucerDestroyed { .. } => self.sfx(&self.bangs[1]),
+            WorldEvent::ShipDestroyed { .. } => self.sfx(&self.explosion),
+            WorldEvent::PowerUp => self.sfx(&self.pickup),
+            WorldEvent::ShieldHit { .. } => self.sfx(&self.shield),
+            WorldEvent::Impact { .. } => {}
+        }
+    }
+
+    // Starts or stops the engine loop.
+    pub fn set_thrust(&mut self, on: bool) {
+        if on == self.thrusting {
+            return;
+        }
+        self.thrusting = on;
+        if on {
+            let volume = self.volume.master * self.volume.sfx;
+            play_sound(
+                &self.thrust,
+                PlaySoundParams {
+                    looped: true,
+                    volume,
+                },
+            );
+        } else {
+            stop_sound(&self.thrust);
+        }
+    }
+
+    // Called whenever the volume settings change.
+    pub fn apply_volume(&self) {
+        if self.thrusting {
+            set_sound_volume(&self.thrust, self.volume.master * self.volume.sfx);
+        }
+    }
+
+    // Advances the two-note heartbeat, which quickens as the field empties.
+    pub fn heartbeat(&mut self, dt: f32, asteroids: usize) {
+        self.next_beat -= dt;
+        if self.next_beat > 0.0 {
+            return;
+        }
+        self.music(&self.beats[self.beat]);
+        self.beat = 1 - self.beat;
+        self.next_beat = (asteroids as f32 * BEAT_PER_ASTEROID).clamp(BEAT_FASTEST, BEAT_SLOWEST);
+    }
+
+    pub fn level_clear(&mut self) {
+        self.stop_loops();
+        self.music(&self.jingle);
+    }
+
+    pub fn stop_loops(&mut self) {
+        self.set_thrust(false);
+        self.next_beat = 0.0;
+    }
+}
+
+async fn sound(samples: &[f32]) -> Sound {
+    load_sound_from_bytes(&wav(samples))
+        .await
+        .expect("synthesised WAV is valid")
+}
+
+// Square wave gliding from `from` to `to` Hz over `secs`, fading out.
+fn sweep(from: f32, to: f32, secs: f32, amplitude: f32) -> Vec<f32> {
+    let len = (secs * SAMPLE_RATE as f32) as usize;
+    let mut phase = 0.0f32;
+    (0..len)
+        .map(|i| {
+            let t = i as f32 / len as f32;
+            phase = (phase + (from + (to - from) * t) / SAMPLE_RATE as f32).fract();
+            let square = if phase < 0.5 { 1.0 } else { -1.0 };
+            square * amplitude * (1.0 - t)
+        })
+        .collect()
+}
+
+// White noise through a one-pole low-pass filter; `smoothing` near 0 gives a
+// deep rumble, near 1 a hiss. Decaying noise is a bang, steady noise a loop.
+fn noise(rng: &mut Rng, secs: f32, smoothing: f32, amplitude: f32, decay: bool) -> Vec<f32> {
+    let len = (secs * SAMPLE_RATE as f32) as usize;
+    let mut level = 0.0f32;
+    (0..len)
+        .map(|i| {
+            level += (rng.gen_range(-1.0f32, 1.0) - level) * smoothing;
+            let envelope = if decay {
+                (1.0 - i as f32 / len as f32).powi(2)
+            } else {
+                1.0
+            };
+            // The filter loses loudness as it smooths, so boost it back.
+            (level * amplitude * envelope / smoothing.sqrt()).clamp(-1.0, 1.0)
+        })
+        .collect()
+}
+
+fn mix(a: &[f32], b: &[f32]) -> Vec<f32> {
+    (0..a.len().max(b.len()))
+        .map(|i| a.get(i).unwrap_or(&0.0) + b.get(i).unwrap_or(&0.0))
+        .collect()
+}
+
+// 16-bit mono PCM.
+fn wav(samples: &[f32]) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let mut out = Vec::with_capacity(44 + data_len as usize);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    out.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for s in samples {
+        let s = (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        out.extend_from_slice(&s.to_le_bytes());
+    }
+    out
+}
@@ -0,0 +1,360 @@
+// Keyboard action map. Each action can have two keys; a key belongs to at
+// most one action. Saved to storage so custom layouts survive restarts.
+use macroquad::input::{KeyCode, is_key_down, is_key_pressed};
+
+const HEADER: &str = "asteroids-bindings 1";
+const BINDINGS_KEY: &str = "bindings.txt";
+pub const SLOTS: usize = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    RotateLeft,
+    RotateRight,
+    Thrust,
+    Fire,
+    Hyperspace,
+    Shield,
+    Pause,
+}
+
+pub const ACTIONS: [Action; 7] = [
+    Action::RotateLeft,
+    Action::RotateRight,
+    Action::Thrust,
+    Action::Fire,
+    Action::Hyperspace,
+    Action::Shield,
+    Action::Pause,
+];
+
+impl Action {
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::RotateLeft => "Rotate left",
+            Action::RotateRight => "Rotate right",
+            Action::Thrust => "Thrust",
+            Action::Fire => "Fire",
+            Action::Hyperspace => "Hyperspace",
+            Action::Shield => "Shield",
+            Action::Pause => "Pause",
+        }
+    }
+}
+
+// Keys that can be bound. Anything else is ignored when rebinding, which also
+// keeps the saved names parseable.
+const BINDABLE: [KeyCode; 70] = {
+    use KeyCode::*;
+    [
+        A,
+        B,
+        C,
+        D,
+        E,
+        F,
+        G,
+        H,
+        I,
+        J,
+        K,
+        L,
+        M,
+        N,
+        O,
+        P,
+        Q,
+        R,
+        S,
+        T,
+        U,
+        V,
+        W,
+        X,
+        Y,
+        Z,
+        Key0,
+        Key1,
+        Key2,
+        Key3,
+        Key4,
+        Key5,
+        Key6,
+        Key7,
+        Key8,
+        Key9,
+        Space,
+        Enter,
+        Tab,
+        Backspace,
+        Escape,
+        Up,
+        Down,
+        Left,
+        Right,
+        LeftShift,
+        RightShift,
+        LeftControl,
+        RightControl,
+        LeftAlt,
+        RightAlt,
+        Comma,
+        Period,
+        Slash,
+        Semicolon,
+        Apostrophe,
+        Minus,
+        Equal,
+        Kp0,
+        Kp1,
+        Kp2,
+        Kp3,
+        Kp4,
+        Kp5,
+        Kp6,
+        Kp7,
+        Kp8,
+        Kp9,
+        KpEnter,
+        Insert,
+    ]
+};
+
+pub fn is_bindable(key: KeyCode) -> bool {
+    BINDABLE.contains(&key)
+}
+
+pub fn key_name(key: KeyCode) -> String {
+    format!("{key:?}")
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    BINDABLE.into_iter().find(|k| key_name(*k) == name)
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Bindings {
+    keys: [[Option<KeyCode>; SLOTS]; ACTIONS.len()],
+}
+
+impl Default for Bindings {
+    fn default() -> Bindings {
+        use KeyCode::*;
+        Bindings {
+            keys: [
+                [Some(Left), Some(A)],
+                [Some(Right), Some(D)],
+                [Some(Up), Some(W)],
+                [Some(Space), None],
+                [Some(Down), Some(S)],
+                [Some(LeftShift), Some(Q)],
+                [Some(Escape), None],
+            ],
+        }
+    }
+}
+
+impl Bindings {
+    // Falls back to the defaults if nothing valid is stored.
+    pub fn load() -> Bindings {
+        crate::storage::load(BINDINGS_KEY)
+            .and_then(|text| Bindings::parse(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        crate::storage::save(BINDINGS_KEY, &self.to_text())
+    }
+
+    pub fn keys(&self, action: Action) -> [Option<KeyCode>; SLOTS] {
+        self.keys[action as usize]
+    }
+
+    pub fn down(&self, action: Action) -> bool {
+        self.keys(action).into_iter().flatten().any(is_key_down)
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.keys(action).into_iter().flatten().any(is_key_pressed)
+    }
+
+    // The action `key` is bound to, if any.
+    pub fn action_for(&self, key: KeyCode) -> Option<Action> {
+        ACTIONS
+            .into_iter()
+            .find(|a| self.keys(*a).contains(&Some(key)))
+    }
+
+    // Binds `key` to `slot` of `action`. A key already used by another
+    // action is a conflict and is returned as the error; one in the other
+    // slot of the same action simply moves.
+    pub fn bind(&mut self, action: Action, slot: usize, key: KeyCode) -> Result<(), Action> {
+        match self.action_for(key) {
+            Some(other) if other != action => return Err(other),
+            _ => {}
+        }
+        for k in self.keys[action as usize].iter_mut() {
+            if *k == Some(key) {
+                *k = None;
+            }
+        }
+        self.keys[action as usize][slot] = Some(key);
+        Ok(())
+    }
+
+    // Pause is the only way out of a running game from the keyboard, so its
+    // last key cannot be cleared.
+    pub fn clear(&mut self, action: Action, slot: usize) -> Result<(), String> {
+        let others = self.keys(action).into_iter().enumerate();
+        if action == Action::Pause && !others.filter(|(s, _)| *s != slot).any(|(_, k)| k.is_some())
+        {
+            return Err("Pause needs a key - bind another one first".to_string());
+        }
+        self.keys[action as usize][slot] = None;
+        Ok(())
+    }
+
+    // "[Left]/[A]" style names of the keys bound to `action`.
+    pub fn describe(&self, action: Action) -> String {
+        let names: Vec<String> = self
+            .keys(action)
+            .into_iter()
+            .flatten()
+            .map(|k| format!("[{}]", key_name(k)))
+            .collect();
+        if names.is_empty() {
+            "[unbound]".to_string()
+        } else {
+            names.join("/")
+        }
+    }
+
+    // One line of help text for the info screen.
+    pub fn help(&self) -> String {
+        format!(
+            "Rotate {} {}, thrust {}, fire {}, jump {}, shield {}, pause {}.",
+            self.describe(Action::RotateLeft),
+            self.describe(Action::RotateRight),
+            self.describe(Action::Thrust),
+            self.describe(Action::Fire),
+            self.describe(Action::Hyperspace),
+            self.describe(Action::Shield),
+            self.describe(Action::Pause),
+        )
+    }
+
+    // One `Action key key` line per action, `-` for an empty slot.
+    pub fn to_text(&self) -> String {
+        let mut out = format!("{HEADER}\n");
+        for action in ACTIONS {
+            out.push_str(&format!("{action:?}"));
+            for key in self.keys(action) {
+                out.push(' ');
+                out.push_str(&key.map_or("-".to_string(), key_name));
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    // Actions missing from the file, such as ones added since it was saved,
+    // keep whichever of their default keys are still free. Conflicting or
+    // unknown keys are an error so a hand-edited file cannot lock anyone out.
+    pub fn parse(text: &str) -> Result<Bindings, String> {
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, l)| l) != Some(HEADER) {
+            return Err("not a bindings file".to_string());
+        }
+        let mut bindings = Bindings::default();
+        let mut seen = [false; ACTIONS.len()];
+        for (n, line) in lines {
+            let bad = || format!("malformed binding on line {}", n + 1);
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let [action, keys @ ..] = parts.as_slice() else {
+                continue;
+            };
+            let action = ACTIONS
+                .into_iter()
+                .find(|a| format!("{a:?}") == *action)
+                .ok_or_else(bad)?;
+            if keys.len() != SLOTS {
+                return Err(bad());
+            }
+            for (slot, name) in keys.iter().enumerate() {
+                let key = if *name == "-" {
+                    None
+                } else {
+                    Some(parse_key(name).ok_or_else(bad)?)
+                };
+                bindings.keys[action as usize][slot] = key;
+            }
+            seen[action as usize] = true;
+        }
+        let taken: Vec<KeyCode> = ACTIONS
+            .into_iter()
+            .filter(|a| seen[*a as usize])
+            .flat_map(|a| bindings.keys(a).into_iter().flatten())
+            .collect();
+        for action in ACTIONS.into_iter().filter(|a| !seen[*a as usize]) {
+            for key in bindings.keys[action as usize].iter_mut() {
+                if key.is_some_and(|k| taken.contains(&k)) {
+                    *key = None;
+                }
+            }
+        }
+        let mut all: Vec<KeyCode> = bindings.keys.iter().flatten().flatten().copied().collect();
+        all.sort_by_key(|k| *k as u16);
+        if all.windows(2).any(|w| w[0] == w[1]) {
+            return Err("conflicting bindings".to_string());
+        }
+        if bindings.keys(Action::Pause).iter().all(Option::is_none) {
+            return Err("nothing bound to Pause".to_string());
+        }
+        Ok(bindings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_survive_a_round_trip() {
+        let defaults = Bindings::default();
+        let parsed = Bindings::parse(&defaults.to_text()).unwrap();
+        assert!(parsed == defaults);
+    }
+
+    #[test]
+    fn refuses_a_key_bound_twice() {
+        let text = Bindings::default()
+            .to_text()
+            .replace("Fire Space -", "Fire Space Left");
+        let err = Bindings::parse(&text).err().unwrap();
+        assert_eq!(err, "conflicting bindings");
+    }
+
+    #[test]
+    fn refuses_an_unbound_pause() {
+        let text = Bindings::default()
+            .to_text()
+            .replace("Pause Escape -", "Pause - -");
+        let err = Bindings::parse(&text).err().unwrap();
+        assert_eq!(err, "nothing bound to Pause");
+    }
+
+    #[test]
+    fn binding_a_taken_key_names_its_action() {
+        let mut bindings = Bindings::default();
+        let before = bindings.clone();
+        assert_eq!(
+            bindings.bind(Action::Fire, 1, KeyCode::Left),
+            Err(Action::RotateLeft)
+        );
+        assert!(bindings == before);
+        assert_eq!(bindings.bind(Action::Fire, 1, KeyCode::F), Ok(()));
+        assert_eq!(
+            bindings.keys(Action::Fire),
+            [Some(KeyCode::Space), Some(KeyCode::F)]
+        );
+    }
+}
@@ -0,0 +1,124 @@
+// Separating axis tests for the convex outlines the game draws: the ship
+// triangle and the saucer hull. A segment counts as a two-point polygon,
+// which is how bullets are swept between two steps. Asteroids are concave
+// but star-shaped around their centre, so they are tested as a fan of
+// triangles.
+use macroquad::math::Vec2;
+
+// True if the convex shapes `a` and `b` overlap. Either may be a segment.
+pub fn polygons_overlap(a: &[Vec2], b: &[Vec2]) -> bool {
+    !has_separating_axis(a, b) && !has_separating_axis(b, a)
+}
+
+// True if a point moving from `from` to `to` touches the convex `poly`.
+pub fn segment_hits_polygon(from: Vec2, to: Vec2, poly: &[Vec2]) -> bool {
+    if from == to {
+        return point_in_polygon(from, poly);
+    }
+    polygons_overlap(&[from, to], poly)
+}
+
+// The triangles from `center` to each edge of the star-shaped `outline`.
+fn fan(center: Vec2, outline: &[Vec2]) -> impl Iterator<Item = [Vec2; 3]> + '_ {
+    (0..outline.len()).map(move |i| [center, outline[i], outline[(i + 1) % outline.len()]])
+}
+
+// True if the star-shaped `outline` around `center` overlaps the convex
+// `other`.
+pub fn star_overlaps(center: Vec2, outline: &[Vec2], other: &[Vec2]) -> bool {
+    fan(center, outline).any(|t| polygons_overlap(&t, other))
+}
+
+// True if a point moving from `from` to `to` touches the star-shaped
+// `outline` around `center`.
+pub fn segment_hits_star(from: Vec2, to: Vec2, center: Vec2, outline: &[Vec2]) -> bool {
+    fan(center, outline).any(|t| segment_hits_polygon(from, to, &t))
+}
+
+// True if two star-shaped outlines overlap.
+pub fn stars_overlap(a_center: Vec2, a: &[Vec2], b_center: Vec2, b: &[Vec2]) -> bool {
+    fan(b_center, b).any(|t| star_overlaps(a_center, a, &t))
+}
+
+// True if `p` is on the same side of every edge of the convex `poly`.
+pub fn point_in_polygon(p: Vec2, poly: &[Vec2]) -> bool {
+    let mut side = 0.0;
+    for i in 0..poly.len() {
+        let c = (poly[(i + 1) % poly.len()] - poly[i]).perp_dot(p - poly[i]);
+        if c == 0.0 {
+            continue;
+        }
+        if side == 0.0 {
+            side = c.signum();
+        } else if c.signum() != side {
+            return false;
+        }
+    }
+    true
+}
+
+// Checks the edge normals of `a` for a gap between the two projections.
+fn has_separating_axis(a: &[Vec2], b: &[Vec2]) -> bool {
+    let edges = if a.len() == 2 { 1 } else { a.len() };
+    (0..edges).any(|i| {
+        let edge = a[(i + 1) % a.len()] - a[i];
+        let axis = edge.perp();
+        let (a_min, a_max) = project(a, axis);
+        let (b_min, b_max) = project(b, axis);
+        a_max < b_min || b_max < a_min
+    })
+}
+
+fn project(poly: &[Vec2], axis: Vec2) -> (f32, f32) {
+    poly.iter()
+        .map(|p| p.dot(axis))
+        .fold((f32::MAX, f32::MIN), |(lo, hi), d| (lo.min(d), hi.max(d)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use macroquad::math::vec2;
+
+    fn triangle(at: Vec2) -> [Vec2; 3] {
+        [at, at + vec2(10.0, 0.0), at + vec2(0.0, 10.0)]
+    }
+
+    #[test]
+    fn triangles_facing_across_a_diagonal_gap_miss() {
+        // The bounding boxes overlap, the hypotenuses do not touch.
+        let a = triangle(Vec2::ZERO);
+        let b = [vec2(10.0, 1.0), vec2(10.0, 10.0), vec2(1.0, 10.0)];
+        assert!(!polygons_overlap(&a, &b));
+    }
+
+    #[test]
+    fn overlapping_triangles_hit() {
+        let a = triangle(Vec2::ZERO);
+        let b = triangle(vec2(4.0, 4.0));
+        assert!(polygons_overlap(&a, &b));
+        assert!(polygons_overlap(&b, &a));
+    }
+
+    #[test]
+    fn fast_segment_through_a_polygon_hits() {
+        // Both ends are well outside, as for a bullet that crossed the
+        // whole shape in one step.
+        let square = [
+            vec2(0.0, 0.0),
+            vec2(10.0, 0.0),
+            vec2(10.0, 10.0),
+            vec2(0.0, 10.0),
+        ];
+        assert!(segment_hits_polygon(
+            vec2(-100.0, 5.0),
+            vec2(100.0, 5.0),
+            &square
+        ));
+        assert!(!segment_hits_polygon(
+            vec2(-100.0, 15.0),
+            vec2(100.0, 15.0),
+            &square
+        ));
+    }
+}
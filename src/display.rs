@@ -0,0 +1,80 @@
+// How the playfield reaches the window. The world has a fixed size of its own
+// (`FIELD_WIDTH` by `FIELD_HEIGHT` for new games), which is scaled to fit the
+// window and centred with black bars on the sides that do not match its
+// shape, so a resize or a turned phone never changes the game itself.
+use macroquad::prelude::*;
+
+const DISPLAY_KEY: &str = "display.txt";
+
+// Screen area a field of `size` is drawn in.
+pub fn viewport(size: Vec2) -> Rect {
+    let scale = (screen_width() / size.x).min(screen_height() / size.y);
+    let (w, h) = (size.x * scale, size.y * scale);
+    Rect::new(
+        (screen_width() - w) / 2.0,
+        (screen_height() - h) / 2.0,
+        w,
+        h,
+    )
+}
+
+// Draws in field coordinates, clipped to the viewport, until `end_field`.
+pub fn begin_field(size: Vec2) {
+    let v = viewport(size);
+    let dpi = screen_dpi_scale();
+    set_camera(&Camera2D {
+        viewport: Some((
+            (v.x * dpi) as i32,
+            (v.y * dpi) as i32,
+            (v.w * dpi) as i32,
+            (v.h * dpi) as i32,
+        )),
+        // `from_display_rect` would draw it upside down outside a render
+        // target, so y is not flipped here.
+        target: size / 2.0,
+        zoom: vec2(2.0 / size.x, 2.0 / size.y),
+        ..Default::default()
+    });
+}
+
+// Back to screen coordinates, with the bars around the field blacked out.
+pub fn end_field(size: Vec2) {
+    set_default_camera();
+    let v = viewport(size);
+    draw_rectangle(0.0, 0.0, v.x, screen_height(), BLACK);
+    draw_rectangle(v.x + v.w, 0.0, v.x, screen_height(), BLACK);
+    draw_rectangle(0.0, 0.0, screen_width(), v.y, BLACK);
+    draw_rectangle(0.0, v.y + v.h, screen_width(), v.y, BLACK);
+}
+
+// Notices the window turning between portrait and landscape, which on a
+// phone means it was rotated and the player needs a moment.
+pub struct Orientation {
+    portrait: bool,
+}
+
+impl Orientation {
+    pub fn new() -> Orientation {
+        Orientation {
+            portrait: screen_height() > screen_width(),
+        }
+    }
+
+    // True on the first frame after the orientation changed.
+    pub fn changed(&mut self) -> bool {
+        let portrait = screen_height() > screen_width();
+        let changed = portrait != self.portrait;
+        self.portrait = portrait;
+        changed
+    }
+}
+
+// Fullscreen unless the player chose a window.
+pub fn load_fullscreen() -> bool {
+    crate::storage::load(DISPLAY_KEY).is_none_or(|t| t.trim() != "windowed")
+}
+
+pub fn save_fullscreen(fullscreen: bool) -> Result<(), String> {
+    let text = if fullscreen { "fullscreen" } else { "windowed" };
+    crate::storage::save(DISPLAY_KEY, text)
+}
@@ -0,0 +1,33 @@
+// Whether the player has looked away from the game. macroquad does not pass
+// focus changes on, so the wasm build asks the page through the
+// `first_game_focus` plugin in index.html, and every build treats a long stall
+// between frames (a minimised window, a closed laptop lid) as lost focus.
+
+// Seconds between frames that count as a stall.
+const STALL: f32 = 0.5;
+
+pub fn lost(frame_time: f32) -> bool {
+    frame_time > STALL || !has_focus()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn has_focus() -> bool {
+    unsafe { page_has_focus() != 0 }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn has_focus() -> bool {
+    true
+}
+
+#[cfg(target_arch = "wasm32")]
+unsafe extern "C" {
+    // 1 while the page is visible and focused.
+    fn page_has_focus() -> i32;
+}
+
+#[cfg(target_arch = "wasm32")]
+#[unsafe(no_mangle)]
+pub extern "C" fn first_game_focus_crate_version() -> u32 {
+    1
+}
@@ -0,0 +1,194 @@
+// The first connected controller, polled once a frame. Buttons follow the W3C
+// "standard gamepad" layout that both gilrs and the browser map pads onto.
+// Native builds need the `gamepad` feature (gilrs, which needs libudev on
+// Linux); the wasm build reads `navigator.getGamepads` through the
+// `first_game_gamepad` plugin in index.html.
+use macroquad::math::Vec2;
+
+// Every slot of the standard layout, in order, so a variant indexes the
+// button array even if the game never reads it.
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    South,
+    East,
+    West,
+    North,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+const BUTTONS: usize = 16;
+// Stick travel ignored around the centre.
+const DEADZONE: f32 = 0.2;
+// How far the stick must be pushed to count as a menu direction.
+const NAV_THRESHOLD: f32 = 0.5;
+
+#[derive(Clone, Copy, Default)]
+struct PadState {
+    connected: bool,
+    stick: Vec2,
+    buttons: [bool; BUTTONS],
+}
+
+pub struct Gamepads {
+    current: PadState,
+    previous: PadState,
+    #[cfg(all(not(target_arch = "wasm32"), feature = "gamepad"))]
+    gilrs: Option<gilrs::Gilrs>,
+}
+
+impl Gamepads {
+    pub fn new() -> Gamepads {
+        Gamepads {
+            current: PadState::default(),
+            previous: PadState::default(),
+            #[cfg(all(not(target_arch = "wasm32"), feature = "gamepad"))]
+            gilrs: gilrs::Gilrs::new().ok(),
+        }
+    }
+
+    pub fn poll(&mut self) {
+        self.previous = self.current;
+        self.current = self.read();
+    }
+
+    pub fn connected(&self) -> bool {
+        self.current.connected
+    }
+
+    pub fn just_disconnected(&self) -> bool {
+        !self.current.connected && self.previous.connected
+    }
+
+    pub fn down(&self, button: Button) -> bool {
+        self.current.buttons[button as usize]
+    }
+
+    pub fn pressed(&self, button: Button) -> bool {
+        self.down(button) && !self.previous.buttons[button as usize]
+    }
+
+    pub fn any_pressed(&self) -> bool {
+        (0..BUTTONS).any(|b| self.current.buttons[b] && !self.previous.buttons[b])
+    }
+
+    // Left stick with the deadzone cut out and the rest rescaled to 0..1,
+    // y pointing down like the screen.
+    pub fn stick(&self) -> Vec2 {
+        let stick = self.current.stick;
+        let len = stick.length();
+        if len < DEADZONE {
+            return Vec2::ZERO;
+        }
+        stick / len * ((len - DEADZONE) / (1.0 - DEADZONE)).min(1.0)
+    }
+
+    // A menu step from the d-pad or from flicking the stick.
+    fn nav(&self, button: Button, axis: fn(Vec2) -> f32) -> bool {
+        let pushed = |s: &PadState| axis(s.stick) > NAV_THRESHOLD;
+        self.pressed(button) || (pushed(&self.current) && !pushed(&self.previous))
+    }
+
+    pub fn nav_up(&self) -> bool {
+        self.nav(Button::DPadUp, |s| -s.y)
+    }
+
+    pub fn nav_down(&self) -> bool {
+        self.nav(Button::DPadDown, |s| s.y)
+    }
+
+    pub fn nav_left(&self) -> bool {
+        self.nav(Button::DPadLeft, |s| -s.x)
+    }
+
+    pub fn nav_right(&self) -> bool {
+        self.nav(Button::DPadRight, |s| s.x)
+    }
+
+    pub fn confirm(&self) -> bool {
+        self.pressed(Button::South) || self.pressed(Button::Start)
+    }
+
+    pub fn back(&self) -> bool {
+        self.pressed(Button::East)
+    }
+
+    #[cfg(all(not(target_arch = "wasm32"), feature = "gamepad"))]
+    fn read(&mut self) -> PadState {
+        use gilrs::{Axis, Button as B};
+
+        let Some(gilrs) = self.gilrs.as_mut() else {
+            return PadState::default();
+        };
+        // Events keep gilrs' cached state and connection list current.
+        while gilrs.next_event().is_some() {}
+        let Some((_, pad)) = gilrs.gamepads().next() else {
+            return PadState::default();
+        };
+        let order = [
+            B::South,
+            B::East,
+            B::West,
+            B::North,
+            B::LeftTrigger,
+            B::RightTrigger,
+            B::LeftTrigger2,
+            B::RightTrigger2,
+            B::Select,
+            B::Start,
+            B::LeftThumb,
+            B::RightThumb,
+            B::DPadUp,
+            B::DPadDown,
+            B::DPadLeft,
+            B::DPadRight,
+        ];
+        PadState {
+            connected: true,
+            stick: Vec2::new(pad.value(Axis::LeftStickX), -pad.value(Axis::LeftStickY)),
+            buttons: order.map(|b| pad.is_pressed(b)),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn read(&mut self) -> PadState {
+        let mut buf = [0.0f32; 2 + BUTTONS];
+        if unsafe { gamepad_read(buf.as_mut_ptr(), buf.len() as u32) } == 0 {
+            return PadState::default();
+        }
+        PadState {
+            connected: true,
+            stick: Vec2::new(buf[0], buf[1]),
+            buttons: std::array::from_fn(|b| buf[2 + b] > 0.5),
+        }
+    }
+
+    #[cfg(all(not(target_arch = "wasm32"), not(feature = "gamepad")))]
+    fn read(&mut self) -> PadState {
+        PadState::default()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+unsafe extern "C" {
+    // Fills `buf` with the stick axes then one value per button, returning
+    // 0 if no pad is connected.
+    fn gamepad_read(buf: *mut f32, len: u32) -> i32;
+}
+
+#[cfg(target_arch = "wasm32")]
+#[unsafe(no_mangle)]
+pub extern "C" fn first_game_gamepad_crate_version() -> u32 {
+    1
+}
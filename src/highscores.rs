@@ -0,0 +1,106 @@
+// Top-ten table of finished runs, kept in storage as one line per entry.
+const HEADER: &str = "asteroids-highscores 1";
+const MAX_ENTRIES: usize = 10;
+
+pub const HIGH_SCORES_KEY: &str = "high_scores.txt";
+pub const NAME_LEN: usize = 3;
+
+#[derive(Clone)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u64,
+    pub level: u32,
+    // Day the run ended, as YYYY-MM-DD.
+    pub date: String,
+}
+
+// Highest score first; ties keep the older entry ahead.
+#[derive(Default)]
+pub struct HighScores {
+    pub entries: Vec<HighScore>,
+}
+
+impl HighScores {
+    // An unreadable or missing table starts out empty.
+    pub fn load() -> HighScores {
+        crate::storage::load(HIGH_SCORES_KEY)
+            .and_then(|text| HighScores::parse(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        crate::storage::save(HIGH_SCORES_KEY, &self.to_text())
+    }
+
+    // True if `score` would make it onto the table.
+    pub fn qualifies(&self, score: u64) -> bool {
+        score > 0
+            && (self.entries.len() < MAX_ENTRIES
+                || self.entries.last().is_some_and(|e| score > e.score))
+    }
+
+    // Adds `entry` in rank order and returns its index, or None if it did
+    // not make the table.
+    pub fn insert(&mut self, entry: HighScore) -> Option<usize> {
+        let rank = self.entries.partition_point(|e| e.score >= entry.score);
+        if rank >= MAX_ENTRIES {
+            return None;
+        }
+        self.entries.insert(rank, entry);
+        self.entries.truncate(MAX_ENTRIES);
+        Some(rank)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = format!("{HEADER}\n");
+        for e in &self.entries {
+            out.push_str(&format!("{} {} {} {}\n", e.name, e.score, e.level, e.date));
+        }
+        out
+    }
+
+    pub fn parse(text: &str) -> Result<HighScores, String> {
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, l)| l) != Some(HEADER) {
+            return Err("not a high score file".to_string());
+        }
+        let mut entries = Vec::new();
+        for (n, line) in lines {
+            let bad = || format!("malformed entry on line {}", n + 1);
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let [name, score, level, date] = parts.as_slice() else {
+                return Err(bad());
+            };
+            entries.push(HighScore {
+                name: name.to_string(),
+                score: score.parse().map_err(|_| bad())?,
+                level: level.parse().map_err(|_| bad())?,
+                date: date.to_string(),
+            });
+        }
+        entries.sort_by_key(|e| std::cmp::Reverse(e.score));
+        entries.truncate(MAX_ENTRIES);
+        Ok(HighScores { entries })
+    }
+}
+
+// Today's date in UTC.
+pub fn today() -> String {
+    let days = (macroquad::miniquad::date::now() / 86400.0).floor() as i64;
+    let (y, m, d) = civil_from_days(days);
+    format!("{y:04}-{m:02}-{d:02}")
+}
+
+// Days since 1970-01-01 to a proleptic Gregorian (year, month, day).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
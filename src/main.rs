@@ -1,281 +1,689 @@
-use macroquad::prelude::*;
-use miniquad::window;
-
-const SHIP_HEIGHT: f32 = 25.;
-const SHIP_BASE: f32 = 22.;
-const FIRE_RATE: f64 = 0.25;
-const SCORE_MULTIPLIER: u8 = 5;
-
-struct Ship {
-    pos: Vec2,
-    rot: f32,
-    vel: Vec2,
-}
+mod audio;
+mod bindings;
+mod collision;
+mod display;
+mod focus;
+mod gamepad;
+mod highscores;
+mod palette;
+mod particles;
+mod powerups;
+mod replay;
+mod rng;
+mod saucer;
+mod storage;
+mod touch;
+mod world;
 
-struct Bullet {
-    pos: Vec2,
-    vel: Vec2,
-    shot_at: f64,
-    collided: bool,
-}
+use audio::{Audio, Volume};
+use bindings::{ACTIONS, Action, Bindings, SLOTS, is_bindable, key_name};
+use display::Orientation;
+use gamepad::{Button, Gamepads};
+use highscores::{HighScore, HighScores, NAME_LEN};
+use macroquad::prelude::*;
+use palette::{ColorMode, ColorPalette, PALETTES_KEY, load_palettes, usable_palettes};
+use particles::Particles;
+use powerups::{PICKUP_RADIUS, POWER_UPS, PowerUpKind};
+use replay::{LAST_REPLAY_KEY, MAX_FRAMES, Replay};
+use rng::fresh_seed;
+use saucer::saucer_outline;
+use touch::{TouchControls, TouchFire, TouchScheme, TouchSettings};
+use world::{
+    Behaviour, EXTRA_LIFE_CHOICES, EXTRA_LIFE_EVERY, FIELD_HEIGHT, FIELD_WIDTH, InputFrame,
+    SHIELD_RADIUS, SHIP_BASE, SHIP_HEIGHT, Ship, TICK, WARP_TIME, World, WorldEvent, ship_vertices,
+};
 
-struct Asteroid {
-    pos: Vec2,
-    vel: Vec2,
-    rot: f32,
-    rot_speed: f32,
-    size: f32,
-    sides: u8,
-    collided: bool,
-    color: Color,
-}
-
-struct ColorPalette {
-    background: Color,
-    ship: Color,
-    asteroid_colors: Vec<Color>,
-}
+const MAX_FRAME_TIME: f32 = 0.25;
+// "on" when asteroids bounce off each other.
+const COLLISIONS_KEY: &str = "collisions.txt";
+// Points between extra lives, one of `EXTRA_LIFE_CHOICES`.
+const EXTRA_LIFE_KEY: &str = "extra_life.txt";
 
+#[derive(Clone, Copy, PartialEq, Eq)]
 enum GameState {
     StartMenu,
     Playing,
     Paused,
+    Settings,
+    Palettes,
     GameOver,
+    EnterName,
     Win,
     InfoScreen,
+    Replay,
+    SoundSettings,
+    Controls,
+    TouchSettings,
+    #[cfg(not(target_arch = "wasm32"))]
     Quit,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PauseItem {
+    Resume,
+    Restart,
+    Settings,
+    MainMenu,
+    #[cfg(not(target_arch = "wasm32"))]
+    Quit,
+}
+
+// Closing the tab is how a browser game quits, so wasm has no Quit.
+const PAUSE_ITEMS: &[PauseItem] = &[
+    PauseItem::Resume,
+    PauseItem::Restart,
+    PauseItem::Settings,
+    PauseItem::MainMenu,
+    #[cfg(not(target_arch = "wasm32"))]
+    PauseItem::Quit,
+];
+
+impl PauseItem {
+    fn label(self) -> &'static str {
+        match self {
+            PauseItem::Resume => "Resume",
+            PauseItem::Restart => "Restart Level",
+            PauseItem::Settings => "Settings",
+            PauseItem::MainMenu => "Main Menu",
+            #[cfg(not(target_arch = "wasm32"))]
+            PauseItem::Quit => "Quit",
+        }
+    }
+}
+
+// Screens reachable from the settings menu, then Back.
+const SETTINGS_ITEMS: [&str; 7] = [
+    "Sound", "Controls", "Touch", "Palettes", "Colours", "Display", "Back",
+];
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum ControlMode {
     Keyboard,
     Touch,
+    Gamepad,
 }
 
-fn wrap_around(v: &Vec2) -> Vec2 {
-    let mut vr = *v;
-    if vr.x > screen_width() {
-        vr.x = 0.;
+fn keyboard_input(bindings: &Bindings) -> InputFrame {
+    let mut input = InputFrame::default();
+    if bindings.down(Action::RotateLeft) {
+        input.rotate -= 1.0;
+    }
+    if bindings.down(Action::RotateRight) {
+        input.rotate += 1.0;
     }
-    if vr.x < 0. {
-        vr.x = screen_width();
+    if bindings.down(Action::Thrust) {
+        input.thrust = 1.0;
     }
-    if vr.y > screen_height() {
-        vr.y = 0.;
+    input.fire = bindings.down(Action::Fire);
+    input.hyperspace = bindings.down(Action::Hyperspace);
+    input.shield = bindings.down(Action::Shield);
+    input
+}
+
+// Rotation follows the stick proportionally; the d-pad turns at full speed.
+fn gamepad_input(pads: &Gamepads) -> InputFrame {
+    let stick = pads.stick();
+    let mut rotate = stick.x;
+    if pads.down(Button::DPadLeft) {
+        rotate -= 1.0;
     }
-    if vr.y < 0. {
-        vr.y = screen_height();
+    if pads.down(Button::DPadRight) {
+        rotate += 1.0;
     }
-    vr
+    let thrust = if pads.down(Button::South) || pads.down(Button::DPadUp) {
+        1.0
+    } else {
+        (-stick.y).max(0.0)
+    };
+    InputFrame {
+        rotate: rotate.clamp(-1.0, 1.0),
+        thrust,
+        fire: pads.down(Button::RightTrigger)
+            || pads.down(Button::RightBumper)
+            || pads.down(Button::West),
+        hyperspace: pads.down(Button::East)
+            || pads.down(Button::LeftTrigger)
+            || pads.down(Button::LeftBumper),
+        shield: pads.down(Button::North),
+    }
+}
+
+// Start menu button that plays back the last finished run.
+fn replay_button() -> Rect {
+    let base = screen_width().min(screen_height());
+    let w = screen_width() * 0.4;
+    let h = base * 0.1;
+    Rect::new(screen_width() / 2.0 - w / 2.0, screen_height() * 0.8, w, h)
+}
+
+// Up arrow, letter and down arrow of each slot on the name entry screen.
+fn name_slots() -> [(Rect, Rect, Rect); NAME_LEN] {
+    let base = screen_width().min(screen_height());
+    let w = base * 0.12;
+    let gap = base * 0.04;
+    let left = screen_width() / 2.0 - (w * NAME_LEN as f32 + gap * (NAME_LEN - 1) as f32) / 2.0;
+    let top = screen_height() / 2.0 - w * 1.5;
+    std::array::from_fn(|i| {
+        let x = left + i as f32 * (w + gap);
+        (
+            Rect::new(x, top, w, w),
+            Rect::new(x, top + w, w, w),
+            Rect::new(x, top + w * 2.0, w, w),
+        )
+    })
 }
 
-fn speed_factor(level_multiplier: f64) -> f32 {
-    1.0 + (level_multiplier as f32) * 0.15
+// OK or Back at the bottom of the name entry and settings screens.
+fn confirm_button() -> Rect {
+    let base = screen_width().min(screen_height());
+    let w = base * 0.3;
+    let h = base * 0.1;
+    Rect::new(screen_width() / 2.0 - w / 2.0, screen_height() * 0.75, w, h)
 }
 
-fn sample_palettes() -> Vec<ColorPalette> {
-    vec![
-        ColorPalette {
-            background: color_u8!(10, 10, 30, 255),
-            ship: color_u8!(0, 255, 255, 255),
-            asteroid_colors: vec![
-                color_u8!(255, 100, 255, 255), // bright magenta
-                color_u8!(100, 255, 100, 255), // neon green
-                color_u8!(255, 255, 100, 255), // soft yellow
-                color_u8!(100, 255, 255, 255), // cyan
-                color_u8!(255, 150, 50, 255),  // orange
-                color_u8!(255, 50, 150, 255),  // pink
-            ],
-        },
-        ColorPalette {
-            background: color_u8!(40, 10, 10, 255),
-            ship: color_u8!(255, 200, 150, 255),
-            asteroid_colors: vec![
-                color_u8!(255, 120, 40, 255),
-                color_u8!(255, 60, 180, 255),
-                color_u8!(255, 220, 60, 255),
-                color_u8!(60, 255, 100, 255),
-                color_u8!(220, 60, 255, 255),
-                color_u8!(255, 80, 80, 255),
-            ],
-        },
-        ColorPalette {
-            background: color_u8!(10, 20, 40, 255),
-            ship: color_u8!(220, 240, 255, 255),
-            asteroid_colors: vec![
-                color_u8!(200, 220, 255, 255),
-                color_u8!(100, 255, 255, 255),
-                color_u8!(255, 100, 255, 255),
-                color_u8!(255, 255, 100, 255),
-                color_u8!(120, 200, 255, 255),
-                color_u8!(255, 120, 200, 255),
-            ],
-        },
-        ColorPalette {
-            background: color_u8!(5, 5, 25, 255),
-            ship: color_u8!(180, 200, 255, 255),
-            asteroid_colors: vec![
-                color_u8!(180, 130, 230, 255),
-                color_u8!(255, 60, 210, 255),
-                color_u8!(220, 220, 80, 255),
-                color_u8!(80, 255, 220, 255),
-                color_u8!(210, 80, 255, 255),
-                color_u8!(255, 180, 60, 255),
-            ],
-        },
-        ColorPalette {
-            background: color_u8!(30, 30, 10, 255),
-            ship: color_u8!(255, 255, 200, 255),
-            asteroid_colors: vec![
-                color_u8!(255, 240, 120, 255),
-                color_u8!(255, 60, 150, 255),
-                color_u8!(60, 255, 180, 255),
-                color_u8!(140, 0, 255, 255),
-                color_u8!(255, 90, 30, 255),
-                color_u8!(50, 200, 120, 255),
-            ],
-        },
-        ColorPalette {
-            background: color_u8!(15, 45, 15, 255),
-            ship: color_u8!(200, 255, 200, 255),
-            asteroid_colors: vec![
-                color_u8!(180, 255, 180, 255),
-                color_u8!(140, 255, 140, 255),
-                color_u8!(220, 255, 180, 255),
-                color_u8!(210, 200, 255, 255),
-                color_u8!(170, 230, 200, 255),
-                color_u8!(190, 240, 210, 255),
-            ],
-        },
-        ColorPalette {
-            background: color_u8!(50, 10, 50, 255),
-            ship: color_u8!(255, 200, 255, 255),
-            asteroid_colors: vec![
-                color_u8!(255, 130, 255, 255),
-                color_u8!(255, 60, 255, 255),
-                color_u8!(255, 220, 240, 255),
-                color_u8!(180, 80, 255, 255),
-                color_u8!(255, 100, 200, 255),
-                color_u8!(255, 150, 240, 255),
-            ],
-        },
-        ColorPalette {
-            background: color_u8!(20, 50, 50, 255),
-            ship: color_u8!(200, 255, 255, 255),
-            asteroid_colors: vec![
-                color_u8!(160, 255, 255, 255),
-                color_u8!(100, 255, 230, 255),
-                color_u8!(255, 100, 255, 255),
-                color_u8!(170, 230, 230, 255),
-                color_u8!(150, 210, 210, 255),
-                color_u8!(255, 50, 255, 255),
-            ],
-        },
-        ColorPalette {
-            background: color_u8!(25, 15, 45, 255),
-            ship: color_u8!(240, 200, 255, 255),
-            asteroid_colors: vec![
-                color_u8!(255, 120, 200, 255),
-                color_u8!(255, 60, 180, 255),
-                color_u8!(255, 0, 255, 255),
-                color_u8!(255, 110, 190, 255),
-                color_u8!(255, 130, 210, 255),
-                color_u8!(255, 150, 230, 255),
-            ],
-        },
-        ColorPalette {
-            background: color_u8!(40, 20, 5, 255),
-            ship: color_u8!(255, 240, 200, 255),
-            asteroid_colors: vec![
-                color_u8!(255, 220, 100, 255),
-                color_u8!(255, 0, 140, 255),
-                color_u8!(255, 240, 140, 255),
-                color_u8!(255, 210, 90, 255),
-                color_u8!(255, 230, 110, 255),
-                color_u8!(255, 245, 130, 255),
-            ],
-        },
-        // Add more palettes as you like
-    ]
+// Settings and options buttons stacked in the top right corner of the start
+// menu.
+fn corner_button(row: usize) -> Rect {
+    let base = screen_width().min(screen_height());
+    let w = screen_width() * 0.2;
+    let h = base * 0.08;
+    Rect::new(
+        screen_width() - w - h * 0.5,
+        h * 0.5 + row as f32 * h * 1.25,
+        w,
+        h,
+    )
 }
 
-fn pick_palette_for_level(level: f64, palettes: &[ColorPalette]) -> &ColorPalette {
-    if (level - 1.0).abs() < std::f64::EPSILON {
-        // first level: use the first palette always
-        &palettes[0]
+// A column of `count` buttons for the pause and settings menus.
+fn menu_rows(count: usize) -> Vec<Rect> {
+    let base = screen_width().min(screen_height());
+    let w = screen_width() * 0.4;
+    let h = base * 0.08;
+    (0..count)
+        .map(|i| {
+            Rect::new(
+                screen_width() / 2.0 - w / 2.0,
+                screen_height() * 0.2 + i as f32 * h * 1.25,
+                w,
+                h,
+            )
+        })
+        .collect()
+}
+
+// The key slots of each action on the controls screen.
+fn binding_slots() -> [[Rect; SLOTS]; ACTIONS.len()] {
+    let base = screen_width().min(screen_height());
+    let h = base * 0.06;
+    let w = screen_width() * 0.18;
+    std::array::from_fn(|row| {
+        let y = screen_height() * 0.18 + row as f32 * h * 1.2;
+        std::array::from_fn(|slot| Rect::new(screen_width() / 2.0 + slot as f32 * w * 1.1, y, w, h))
+    })
+}
+
+// The layout and fire mode rows on the touch settings screen.
+fn touch_rows() -> [Rect; 2] {
+    let base = screen_width().min(screen_height());
+    let w = screen_width() * 0.5;
+    let h = base * 0.12;
+    std::array::from_fn(|i| {
+        Rect::new(
+            screen_width() / 2.0 - w / 2.0,
+            screen_height() * 0.3 + i as f32 * h * 1.5,
+            w,
+            h,
+        )
+    })
+}
+
+// Reset and Back on the controls screen.
+fn controls_buttons() -> (Rect, Rect) {
+    let back = confirm_button();
+    let reset = Rect::new(back.x - back.w * 1.2, back.y, back.w, back.h);
+    (
+        reset,
+        Rect::new(back.x + back.w * 0.2, back.y, back.w, back.h),
+    )
+}
+
+// The minus and plus buttons of each row on the sound settings screen.
+fn volume_rows() -> [(Rect, Rect); 3] {
+    let base = screen_width().min(screen_height());
+    let size = base * 0.1;
+    let top = screen_height() * 0.3;
+    std::array::from_fn(|i| {
+        let y = top + i as f32 * size * 1.5;
+        (
+            Rect::new(screen_width() / 2.0, y, size, size),
+            Rect::new(screen_width() / 2.0 + size * 3.0, y, size, size),
+        )
+    })
+}
+
+fn draw_centered(text: &str, rect: Rect, font_size: f32, color: Color) {
+    let ts = measure_text(text, None, font_size as u16, 1.0);
+    draw_text(
+        text,
+        rect.x + rect.w / 2.0 - ts.width / 2.0,
+        rect.y + rect.h / 2.0 + ts.height / 2.0,
+        font_size,
+        color,
+    );
+}
+
+// The table as it appears on the start menu, `highlight` marking the entry
+// that was just added.
+fn draw_high_scores(scores: &HighScores, top: f32, bottom: f32, highlight: Option<usize>) {
+    let row = (bottom - top) / 11.0;
+    let fs = row * 0.9;
+    let x = screen_width() / 2.0
+        - measure_text("00. AAA 0000000  L00  0000-00-00", None, fs as u16, 1.0).width / 2.0;
+    draw_text("HIGH SCORES", x, top + row, fs, DARKGRAY);
+    if scores.entries.is_empty() {
+        draw_text("No runs yet", x, top + row * 2.0, fs, GRAY);
+    }
+    for (i, e) in scores.entries.iter().enumerate() {
+        let color = if highlight == Some(i) {
+            BLACK
+        } else {
+            DARKGRAY
+        };
+        let line = format!(
+            "{:>2}. {} {:>7}  L{:<2}  {}",
+            i + 1,
+            e.name,
+            e.score,
+            e.level,
+            e.date
+        );
+        draw_text(&line, x, top + row * (i as f32 + 2.0), fs, color);
+    }
+}
+
+// Characters typed since the last frame, oldest first. macroquad hands them
+// out newest first.
+fn typed_chars() -> Vec<char> {
+    let mut chars: Vec<char> = std::iter::from_fn(get_char_pressed).collect();
+    chars.reverse();
+    chars
+}
+
+fn clicked(rect: Rect) -> bool {
+    if is_mouse_button_pressed(MouseButton::Left) && rect.contains(mouse_position().into()) {
+        return true;
+    }
+    touches()
+        .iter()
+        .any(|t| t.phase == TouchPhase::Started && rect.contains(t.position))
+}
+
+// `alpha` is how far the frame is between the last two simulation steps.
+// With `fade_from`, colours cross-fade from that palette while the wave
+// spawns in.
+fn draw_world(world: &World, palette: &ColorPalette, fade_from: Option<&ColorPalette>, alpha: f32) {
+    let ship = &world.ship;
+    let ship_pos = world.interpolate(ship.prev_pos, ship.pos, alpha);
+    let ship_rot = ship.prev_rot + (ship.rot - ship.prev_rot) * alpha;
+    // Hidden while waiting to respawn or in hyperspace, blinking while
+    // invulnerable.
+    let ship_visible = world.ship_lost_at.is_none()
+        && ship.warp <= 0.0
+        && ((ship.invulnerable * 8.0) as u32).is_multiple_of(2);
+
+    let shown = match fade_from {
+        Some(from) => from.blend(palette, world.spawn_in()),
+        None => palette.clone(),
+    };
+    let ship_color = shown.ship;
+    clear_background(shown.background);
+    // Objects straddling an edge are drawn again on the opposite side.
+    for b in world.projectiles.iter() {
+        let pos = world.interpolate(b.prev_pos, b.pos, alpha);
+        let dir = b.vel.normalize_or_zero();
+        match b.behaviour {
+            _ if b.from_saucer => draw_circle_lines(pos.x, pos.y, 2.5, 1.0, ship_color),
+            Behaviour::Straight => draw_circle(pos.x, pos.y, 2.0, ship_color),
+            Behaviour::Beam => {
+                let tail = pos - dir * 14.0;
+                draw_line(tail.x, tail.y, pos.x, pos.y, 2.0, ship_color);
+            }
+            Behaviour::Homing => {
+                let tail = pos - dir * 6.0;
+                draw_line(tail.x, tail.y, pos.x, pos.y, 3.0, ship_color);
+            }
+        }
+    }
+    // Pickups blink for their last two seconds.
+    let now = world.time();
+    for p in world.power_ups.iter() {
+        let left = p.expires_at - now;
+        if left < 2.0 && ((left * 8.0) as u32).is_multiple_of(2) {
+            continue;
+        }
+        draw_circle_lines(p.pos.x, p.pos.y, PICKUP_RADIUS, 1.5, ship_color);
+        let rect = Rect::new(
+            p.pos.x - PICKUP_RADIUS,
+            p.pos.y - PICKUP_RADIUS,
+            PICKUP_RADIUS * 2.0,
+            PICKUP_RADIUS * 2.0,
+        );
+        draw_centered(p.kind.letter(), rect, PICKUP_RADIUS * 1.6, ship_color);
+    }
+    // A new wave grows and fades in while it is frozen.
+    let spawn_in = world.spawn_in();
+    for a in world.asteroids.iter() {
+        let pos = world.interpolate(a.prev_pos, a.pos, alpha);
+        let rot = a.prev_rot + (a.rot - a.prev_rot) * alpha;
+        let mut color = palette
+            .asteroid_colors
+            .iter()
+            .position(|c| *c == a.color)
+            .map_or(a.color, |i| shown.asteroid_colors[i]);
+        color.a *= spawn_in;
+        let size = a.size * (0.5 + 0.5 * spawn_in);
+        for p in world.ghost_positions(pos, a.size) {
+            draw_outline(&a.outline_at(p, rot, size), true, color);
+        }
+    }
+    for s in world.saucers.iter() {
+        let pos = world.interpolate(s.prev_pos, s.pos, alpha);
+        // Saucers leave at the side edges instead of wrapping, so only the
+        // vertical copies are drawn.
+        for p in world.ghost_positions(pos, s.radius()) {
+            if p.x != pos.x {
+                continue;
+            }
+            let (hull, dome) = saucer_outline(p, s.radius());
+            draw_outline(&hull, true, ship_color);
+            draw_outline(&dome, false, ship_color);
+            draw_line(hull[0].x, hull[0].y, hull[3].x, hull[3].y, 2.0, ship_color);
+        }
+    }
+    if ship_visible {
+        for p in world.ghost_positions(ship_pos, SHIP_HEIGHT) {
+            let [nose, v2, v3] = ship_vertices(p, ship_rot);
+            draw_triangle_lines(nose, v2, v3, 2.0, ship_color);
+            // The power-up shield flickers as it runs out.
+            let shield = world.power(PowerUpKind::Shield);
+            if ship.shielded
+                || shield > 0.0 && (shield > 2.0 || ((shield * 8.0) as u32).is_multiple_of(2))
+            {
+                draw_circle_lines(p.x, p.y, SHIELD_RADIUS, 1.5, ship_color);
+            }
+            if ship.thrusting {
+                // Flickering exhaust out of the middle of the base.
+                let back = (v2 + v3) / 2.0;
+                let tip =
+                    back + (back - nose).normalize() * SHIP_HEIGHT * rand::gen_range(0.3, 0.7);
+                let left = back + (v2 - back) * 0.6;
+                let right = back + (v3 - back) * 0.6;
+                draw_line(left.x, left.y, tip.x, tip.y, 2.0, ship_color);
+                draw_line(right.x, right.y, tip.x, tip.y, 2.0, ship_color);
+            }
+        }
+    }
+    draw_warp(ship, ship_color);
+}
+
+// Particles for one world event, in the colours `draw_world` uses.
+fn spawn_particles(particles: &mut Particles, event: &WorldEvent, palette: &ColorPalette) {
+    let ship_color = palette.ship;
+    match event {
+        WorldEvent::AsteroidBroken {
+            outline,
+            vel,
+            color,
+            ..
+        } => particles.debris(outline, *vel, *color),
+        WorldEvent::SaucerDestroyed { pos, radius } => {
+            particles.debris(&saucer_outline(*pos, *radius).0, Vec2::ZERO, ship_color)
+        }
+        WorldEvent::ShipDestroyed { outline, vel } => particles.wreckage(outline, *vel, ship_color),
+        WorldEvent::Impact { pos, vel } => particles.sparks(*pos, *vel, ship_color),
+        WorldEvent::ShieldHit { pos, vel } => particles.sparks(*pos, -*vel, ship_color),
+        WorldEvent::Fire | WorldEvent::PowerUp => {}
+    }
+}
+
+fn draw_outline(points: &[Vec2], closed: bool, color: Color) {
+    let segments = if closed {
+        points.len()
     } else {
-        let idx = rand::gen_range(0, palettes.len() as i32) as usize;
-        &palettes[idx]
+        points.len() - 1
+    };
+    for i in 0..segments {
+        let a = points[i];
+        let b = points[(i + 1) % points.len()];
+        draw_line(a.x, a.y, b.x, b.y, 2.0, color);
     }
 }
 
-fn random_asteroid_color(palette: &ColorPalette) -> Color {
-    let i = rand::gen_range(0, palette.asteroid_colors.len() as i32) as usize;
-    palette.asteroid_colors[i]
+// Rings collapsing where the ship left, then expanding where it arrives.
+fn draw_warp(ship: &Ship, color: Color) {
+    if ship.warp <= 0.0 {
+        return;
+    }
+    let t = 1.0 - ship.warp / WARP_TIME;
+    let (pos, scale) = if t < 0.5 {
+        (ship.warp_from, 1.0 - t * 2.0)
+    } else {
+        (ship.pos, t * 2.0 - 1.0)
+    };
+    for i in 1..=3 {
+        let r = SHIP_HEIGHT * scale * i as f32 / 2.0;
+        draw_circle_lines(
+            pos.x,
+            pos.y,
+            r,
+            1.0,
+            color.with_alpha(1.0 - i as f32 * 0.25),
+        );
+    }
 }
 
-fn new_game(
-    level_multiplier: f64,
-    palette: &ColorPalette,
-) -> (Ship, Vec<Bullet>, Vec<Asteroid>, f64, u64) {
-    let ship = Ship {
-        pos: vec2(screen_width() / 2.0, screen_height() / 2.0),
-        rot: 0.0,
-        vel: Vec2::ZERO,
+fn draw_hud(world: &World) {
+    let base = screen_width().min(screen_height());
+    let score_and_level_fs = base * 0.04;
+    let alpha = 0.75;
+
+    // Drawing Score
+    let msg = world.player_score.to_string();
+    let ts = measure_text(msg.as_str(), None, score_and_level_fs as u16, 1.0);
+    draw_text(
+        msg.as_str(),
+        screen_width() / 4.0,
+        ts.height * 2.0,
+        score_and_level_fs,
+        DARKGRAY.with_alpha(alpha),
+    );
+
+    // Drawing remaining lives as ship icons after the score
+    let icon_y = ts.height * 2.0 - ts.height / 2.0;
+    for i in 0..world.lives {
+        let x = screen_width() / 4.0 + ts.width + SHIP_BASE * (i as f32 + 1.5);
+        let [nose, v2, v3] = ship_vertices(vec2(x, icon_y), 0.0);
+        draw_triangle_lines(nose, v2, v3, 2.0, DARKGRAY.with_alpha(alpha));
+    }
+
+    // Drawing the shield energy bar under the score
+    let fs = score_and_level_fs * 0.6;
+    let bar = Rect::new(
+        screen_width() / 4.0,
+        ts.height * 2.0 + fs * 0.6,
+        base * 0.2,
+        fs * 0.5,
+    );
+    // Fainter while it recharges from empty and cannot be raised.
+    let fill = if world.ship.depleted {
+        alpha * 0.4
+    } else {
+        alpha
     };
-    let bullets = Vec::new();
-    let mut asteroids = Vec::new();
-    let center = vec2(screen_width() / 2.0, screen_height() / 2.0);
-    let factor = speed_factor(level_multiplier);
-    for _ in 0..(5. + (level_multiplier * 2.)) as u32 {
-        let base_vel = vec2(rand::gen_range(-1., 1.), rand::gen_range(-1., 1.));
-        let norm = if base_vel.length() == 0. {
-            vec2(1.0, 0.0)
-        } else {
-            base_vel.normalize()
-        };
-        asteroids.push(Asteroid {
-            pos: center
-                + vec2(rand::gen_range(-1., 1.), rand::gen_range(-1., 1.)).normalize()
-                    * screen_width().min(screen_height())
-                    / 2.0,
-            vel: norm * factor,
-            rot: 0.0,
-            rot_speed: rand::gen_range(-2., 2.),
-            size: screen_width().min(screen_height()) / 10.0,
-            sides: rand::gen_range(3u8, 8u8),
-            collided: false,
-            color: random_asteroid_color(palette),
-        });
+    draw_rectangle(
+        bar.x,
+        bar.y,
+        bar.w * world.ship.energy,
+        bar.h,
+        DARKGRAY.with_alpha(fill),
+    );
+    draw_rectangle_lines(bar.x, bar.y, bar.w, bar.h, 1.0, DARKGRAY.with_alpha(alpha));
+
+    // Drawing running power-ups under that, seconds left rounded up
+    let mut y = bar.y + bar.h + fs * 1.2;
+    for kind in POWER_UPS {
+        let left = world.power(kind);
+        if left <= 0.0 {
+            continue;
+        }
+        let msg = format!("{} {}", kind.label(), left.ceil());
+        draw_text(
+            msg.as_str(),
+            screen_width() / 4.0,
+            y,
+            fs,
+            DARKGRAY.with_alpha(alpha),
+        );
+        y += fs;
     }
-    let last_shot = get_time();
-    let player_score = 0;
-    (ship, bullets, asteroids, last_shot, player_score)
+
+    // Drawing Level
+    let msg = format!("Level: {}", world.level_multiplier);
+    let ts = measure_text(msg.as_str(), None, score_and_level_fs as u16, 1.0);
+    draw_text(
+        msg.as_str(),
+        screen_width() * 0.75,
+        ts.height * 2.0,
+        score_and_level_fs,
+        DARKGRAY.with_alpha(alpha),
+    );
 }
 
 fn conf() -> Conf {
     Conf {
         window_title: "Asteroids".to_string(),
-        fullscreen: true,
+        fullscreen: display::load_fullscreen(),
         ..Default::default()
     }
 }
 
-#[macroquad::main(conf)]
-async fn main() {
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if let [_, flag, path] = args.as_slice()
+        && flag == "--verify-replay"
+    {
+        std::process::exit(verify_replay(path));
+    }
+    macroquad::Window::from_config(conf(), run());
+}
+
+// Replays a recording without opening a window and reports whether it
+// reaches the recorded score. Exit code 0 on a match.
+fn verify_replay(path: &str) -> i32 {
+    let replay = match std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| Replay::parse(&text))
+    {
+        Ok(replay) => replay,
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            return 2;
+        }
+    };
+    let score = replay.simulate(&load_palettes().0);
+    if score == replay.final_score {
+        println!(
+            "replay OK: score {score} over {} ticks",
+            replay.inputs.len()
+        );
+        0
+    } else {
+        println!(
+            "replay MISMATCH: recorded score {}, replayed score {score}",
+            replay.final_score
+        );
+        1
+    }
+}
+
+async fn run() {
     for _ in 0..3 {
         next_frame().await;
     }
 
-    let palettes = sample_palettes();
-    let mut level_multiplier = 1.0;
-    let mut current_palette = pick_palette_for_level(level_multiplier, &palettes);
-    let (mut ship, mut bullets, mut asteroids, mut last_shot, mut player_score) =
-        new_game(level_multiplier, current_palette);
+    // A broken user theme falls back to the built-in one; the reason is shown
+    // on the palettes screen. Levels only use the palettes that pass the
+    // colour mode's contrast check.
+    let (all_palettes, palette_error) = load_palettes();
+    if let Some(e) = &palette_error {
+        error!("{}", e);
+    }
+    let mut color_mode = ColorMode::load();
+    let mut palettes = usable_palettes(&all_palettes, color_mode);
+    let custom_theme = storage::load(PALETTES_KEY).is_some();
+    let mut world = World::new(fresh_seed(), &palettes, FIELD_WIDTH, FIELD_HEIGHT);
+    // Digits typed on the start menu; empty means a fresh random seed.
+    let mut seed_entry = String::new();
     let mut game_state = GameState::StartMenu;
     let mut control_mode = ControlMode::Keyboard;
+    let mut accumulator = 0.0;
+    let mut recording = Replay::new(&world);
+    let mut last_replay = storage::load(LAST_REPLAY_KEY).and_then(|text| Replay::parse(&text).ok());
+    let mut replay_tick = 0;
+    let mut high_scores = HighScores::load();
+    // Letters on the name entry screen, kept for the next high score.
+    let mut name = [b'A'; NAME_LEN];
+    let mut name_cursor = 0;
+    let mut new_rank = None;
+    let mut audio = Audio::load(Volume::load()).await;
+    let mut gamepads = Gamepads::new();
+    let mut bindings = Bindings::load();
+    let mut touch = TouchControls::new(TouchSettings::load());
+    // Highlighted row on the touch settings screen.
+    let mut touch_row = 0;
+    // Selected action and key slot on the controls screen, whether it is
+    // waiting for a key, and the last hint or conflict shown there.
+    let mut controls_row = 0;
+    let mut controls_slot = 0;
+    let mut listening = false;
+    let mut controls_msg = String::new();
+    let mut controls_conflict = false;
+    // Set by the first press of reset, which only asks to press it again.
+    let mut confirm_reset = false;
+    let mut particles = Particles::new();
+    // Highlighted row on the sound settings screen.
+    let mut sound_row = 0;
+    // Highlighted rows on the pause and settings menus, and where the
+    // settings screens go back to.
+    let mut pause_row = 0;
+    let mut settings_row = 0;
+    let mut settings_return = GameState::StartMenu;
+    // The world and recording length as the current level began, for
+    // Restart Level. Taken on the first frame of play.
+    let mut level_start: Option<(World, usize)> = None;
+    // Palette shown on the palettes screen, and the one the current level is
+    // fading in from.
+    let mut palette_view = 0;
+    let mut fade_from: Option<ColorPalette> = None;
+    let mut fullscreen = display::load_fullscreen();
+    // Turning a phone mid-game pauses it.
+    let mut orientation = Orientation::new();
+    let mut shown_state = game_state;
+    let mut asteroid_collisions = storage::load(COLLISIONS_KEY).is_some_and(|t| t.trim() == "on");
+    let mut extra_life_every = storage::load(EXTRA_LIFE_KEY)
+        .and_then(|t| t.trim().parse().ok())
+        .filter(|n| EXTRA_LIFE_CHOICES.contains(n))
+        .unwrap_or(EXTRA_LIFE_EVERY);
 
     loop {
+        gamepads.poll();
+        let rotated = orientation.changed();
+        // Keys typed elsewhere must not turn up in the seed on the way back.
+        if game_state != shown_state && game_state == GameState::StartMenu {
+            clear_input_queue();
+        }
+        shown_state = game_state;
         match game_state {
             GameState::StartMenu => {
                 clear_background(LIGHTGRAY);
@@ -283,30 +691,171 @@ async fn main() {
                 let fs_title = base * 0.05;
                 let fs_prompt = base * 0.04;
 
+                // A digit that would take the seed past u64::MAX is refused, so
+                // what is shown is always the seed that gets played.
+                for c in typed_chars() {
+                    let typed = format!("{seed_entry}{c}");
+                    if c.is_ascii_digit() && typed.parse::<u64>().is_ok() {
+                        seed_entry = typed;
+                    }
+                }
+                if is_key_pressed(KeyCode::Backspace) {
+                    seed_entry.pop();
+                }
+
                 let welcome = "Asteroids - Lovingly cloned by patdalcia <3";
                 let prompt = "Press [Enter] or tap screen to start with touch";
+                let seed_msg = if seed_entry.is_empty() {
+                    "Type a seed to replay a run".to_string()
+                } else {
+                    format!("Seed: {seed_entry}")
+                };
+
+                if let Some(replay) = &last_replay {
+                    let btn = replay_button();
+                    draw_rectangle_lines(btn.x, btn.y, btn.w, btn.h, 2.0, DARKGRAY);
+                    let label = "Watch replay [R]";
+                    let ts = measure_text(label, None, fs_prompt as u16, 1.0);
+                    draw_text(
+                        label,
+                        btn.x + btn.w / 2.0 - ts.width / 2.0,
+                        btn.y + btn.h / 2.0 + ts.height / 2.0,
+                        fs_prompt,
+                        DARKGRAY,
+                    );
+                    if is_key_pressed(KeyCode::R) || gamepads.pressed(Button::North) || clicked(btn)
+                    {
+                        world = replay.start(&palettes);
+                        fade_from = None;
+                        particles.clear();
+                        replay_tick = 0;
+                        accumulator = 0.0;
+                        game_state = GameState::Replay;
+                        next_frame().await;
+                        continue;
+                    }
+                }
+
+                let btn = corner_button(0);
+                draw_rectangle_lines(btn.x, btn.y, btn.w, btn.h, 2.0, DARKGRAY);
+                draw_centered("Sound [V]", btn, fs_prompt, DARKGRAY);
+                if is_key_pressed(KeyCode::V) || gamepads.pressed(Button::Select) || clicked(btn) {
+                    sound_row = 0;
+                    settings_return = GameState::StartMenu;
+                    game_state = GameState::SoundSettings;
+                    next_frame().await;
+                    continue;
+                }
+                let btn = corner_button(2);
+                draw_rectangle_lines(btn.x, btn.y, btn.w, btn.h, 2.0, DARKGRAY);
+                draw_centered("Touch [T]", btn, fs_prompt, DARKGRAY);
+                if is_key_pressed(KeyCode::T) || clicked(btn) {
+                    touch_row = 0;
+                    settings_return = GameState::StartMenu;
+                    game_state = GameState::TouchSettings;
+                    next_frame().await;
+                    continue;
+                }
+                let btn = corner_button(1);
+                draw_rectangle_lines(btn.x, btn.y, btn.w, btn.h, 2.0, DARKGRAY);
+                draw_centered("Controls [C]", btn, fs_prompt, DARKGRAY);
+                if is_key_pressed(KeyCode::C) || clicked(btn) {
+                    controls_row = 0;
+                    controls_slot = 0;
+                    controls_msg.clear();
+                    confirm_reset = false;
+                    settings_return = GameState::StartMenu;
+                    game_state = GameState::Controls;
+                    next_frame().await;
+                    continue;
+                }
+                let btn = corner_button(3);
+                let label = if asteroid_collisions {
+                    "Bounce: On [B]"
+                } else {
+                    "Bounce: Off [B]"
+                };
+                draw_rectangle_lines(btn.x, btn.y, btn.w, btn.h, 2.0, DARKGRAY);
+                draw_centered(label, btn, fs_prompt, DARKGRAY);
+                if is_key_pressed(KeyCode::B) || clicked(btn) {
+                    asteroid_collisions = !asteroid_collisions;
+                    let text = if asteroid_collisions { "on" } else { "off" };
+                    if let Err(e) = storage::save(COLLISIONS_KEY, text) {
+                        error!("saving collisions option failed: {}", e);
+                    }
+                    next_frame().await;
+                    continue;
+                }
+                let btn = corner_button(4);
+                let label = match extra_life_every {
+                    0 => "1UP: Off [E]".to_string(),
+                    n => format!("1UP: {n} [E]"),
+                };
+                draw_rectangle_lines(btn.x, btn.y, btn.w, btn.h, 2.0, DARKGRAY);
+                draw_centered(&label, btn, fs_prompt, DARKGRAY);
+                if is_key_pressed(KeyCode::E) || clicked(btn) {
+                    let i = EXTRA_LIFE_CHOICES
+                        .iter()
+                        .position(|n| *n == extra_life_every)
+                        .unwrap_or(0);
+                    extra_life_every = EXTRA_LIFE_CHOICES[(i + 1) % EXTRA_LIFE_CHOICES.len()];
+                    if let Err(e) = storage::save(EXTRA_LIFE_KEY, &extra_life_every.to_string()) {
+                        error!("saving extra life option failed: {}", e);
+                    }
+                    next_frame().await;
+                    continue;
+                }
 
                 let ts_w = measure_text(welcome, None, fs_title as u16, 1.0);
                 let ts_p = measure_text(prompt, None, fs_prompt as u16, 1.0);
+                let ts_s = measure_text(seed_msg.as_str(), None, fs_prompt as u16, 1.0);
 
                 draw_text(
                     welcome,
                     screen_width() / 2.0 - ts_w.width / 2.0,
-                    screen_height() / 2.0 - ts_w.height - 20.0,
+                    screen_height() * 0.12,
                     fs_title,
                     DARKGRAY,
                 );
                 draw_text(
                     prompt,
                     screen_width() / 2.0 - ts_p.width / 2.0,
-                    screen_height() / 2.0 + 20.0,
+                    screen_height() * 0.12 + ts_w.height + 20.0,
                     fs_prompt,
                     DARKGRAY,
                 );
+                draw_text(
+                    seed_msg.as_str(),
+                    screen_width() / 2.0 - ts_s.width / 2.0,
+                    screen_height() * 0.12 + ts_w.height + 20.0 + ts_p.height * 2.0,
+                    fs_prompt,
+                    DARKGRAY,
+                );
+                draw_high_scores(
+                    &high_scores,
+                    screen_height() * 0.3,
+                    screen_height() * 0.78,
+                    new_rank,
+                );
+
+                if gamepads.connected() {
+                    let join = "Controller connected - press any button to play";
+                    let ts = measure_text(join, None, fs_prompt as u16, 1.0);
+                    draw_text(
+                        join,
+                        screen_width() / 2.0 - ts.width / 2.0,
+                        screen_height() * 0.96,
+                        fs_prompt,
+                        DARKGRAY,
+                    );
+                }
 
                 if is_key_pressed(KeyCode::Enter) {
                     control_mode = ControlMode::Keyboard;
                     game_state = GameState::InfoScreen;
+                } else if gamepads.any_pressed() {
+                    control_mode = ControlMode::Gamepad;
+                    game_state = GameState::InfoScreen;
                 } else {
                     for touch in touches() {
                         if touch.phase == TouchPhase::Started {
@@ -321,356 +870,302 @@ async fn main() {
             }
 
             GameState::Playing => {
-                let now = get_time();
-                let mut acc = -ship.vel / 100.0;
-
-                if control_mode == ControlMode::Keyboard {
-                    if is_key_down(KeyCode::Left) || is_key_down(KeyCode::A) {
-                        ship.rot -= 5.0;
-                    }
-                    if is_key_down(KeyCode::Right) || is_key_down(KeyCode::D) {
-                        ship.rot += 5.0;
+                if level_start.is_none() {
+                    level_start = Some((world.clone(), recording.inputs.len()));
+                }
+                let input = match control_mode {
+                    ControlMode::Keyboard => keyboard_input(&bindings),
+                    ControlMode::Touch => {
+                        touch.input(world.ship.rot, get_frame_time().min(MAX_FRAME_TIME))
                     }
-                    if is_key_down(KeyCode::Up) || is_key_down(KeyCode::W) {
-                        let ang = ship.rot.to_radians();
-                        acc = vec2(ang.sin(), -ang.cos()) * 2.0;
+                    ControlMode::Gamepad => gamepad_input(&gamepads),
+                };
+                let pause = match control_mode {
+                    ControlMode::Keyboard => bindings.pressed(Action::Pause),
+                    ControlMode::Touch => clicked(touch::pause_button()),
+                    // Pulling the pad out pauses rather than leaving the ship adrift.
+                    ControlMode::Gamepad => {
+                        gamepads.pressed(Button::Start) || gamepads.just_disconnected()
                     }
-                    if is_key_down(KeyCode::Space) && (now - last_shot > FIRE_RATE) {
-                        let ang = ship.rot.to_radians();
-                        let dir = vec2(ang.sin(), -ang.cos());
-                        bullets.push(Bullet {
-                            pos: ship.pos + dir * (SHIP_HEIGHT / 2.0),
-                            vel: dir * 7.0,
-                            shot_at: now,
-                            collided: false,
-                        });
-                        last_shot = now;
+                };
+                // Looking away from the game pauses it too.
+                if pause || rotated || focus::lost(get_frame_time()) {
+                    audio.stop_loops();
+                    pause_row = 0;
+                    game_state = GameState::Paused;
+                }
+
+                // Clamped so a long stall (e.g. a backgrounded tab) does not
+                // fast-forward the game.
+                let frame_time = get_frame_time().min(MAX_FRAME_TIME);
+                accumulator += frame_time;
+                while accumulator >= TICK {
+                    world.step(&input, TICK);
+                    if recording.inputs.len() < MAX_FRAMES {
+                        recording.inputs.push(input);
+                    } else {
+                        recording.too_long = true;
                     }
-                    if is_key_down(KeyCode::Escape) {
-                        game_state = GameState::Paused;
+                    accumulator -= TICK;
+                    for event in &world.events {
+                        audio.play(event);
+                        spawn_particles(&mut particles, event, &palettes[world.palette]);
                     }
-                }
 
-                if control_mode == ControlMode::Touch {
-                    let scr_w = screen_width();
-                    let scr_h = screen_height();
-                    let btn_size = scr_w * 0.2;
-                    let rotation_btn_w = scr_w / 4.;
-
-                    let left_btn = Rect::new(0.0, scr_h - btn_size, rotation_btn_w, btn_size);
-                    let right_btn =
-                        Rect::new(rotation_btn_w, scr_h - btn_size, rotation_btn_w, btn_size);
-                    let thrust_btn = Rect::new(scr_w / 2., scr_h - btn_size, scr_w / 2., btn_size);
-
-                    // Auto fire for touch
-                    if now - last_shot > FIRE_RATE {
-                        let ang = ship.rot.to_radians();
-                        let dir = vec2(ang.sin(), -ang.cos());
-                        bullets.push(Bullet {
-                            pos: ship.pos + dir * (SHIP_HEIGHT / 2.0),
-                            vel: dir * 7.0,
-                            shot_at: now,
-                            collided: false,
-                        });
-                        last_shot = now;
-                    }
-
-                    for touch in touches().iter() {
-                        let p = touch.position;
-                        if left_btn.contains(p) {
-                            ship.rot -= 3.0;
-                        } else if right_btn.contains(p) {
-                            ship.rot += 3.0;
-                        } else if thrust_btn.contains(p) {
-                            let ang = ship.rot.to_radians();
-                            acc = vec2(ang.sin(), -ang.cos()) * 0.25;
+                    if world.game_over {
+                        recording.final_score = world.player_score;
+                        let finished = std::mem::replace(&mut recording, Replay::new(&world));
+                        // A run past the length cap keeps the previous replay.
+                        if !finished.too_long {
+                            if let Err(e) = storage::save(LAST_REPLAY_KEY, &finished.to_text()) {
+                                error!("saving replay failed: {}", e);
+                            }
+                            last_replay = Some(finished);
                         }
-
-                        // Uncomment to enable tap to shoot
-                        // else if now - last_shot > FIRE_RATE {
-                        //     let ang = ship.rot.to_radians();
-                        //     let dir = vec2(ang.sin(), -ang.cos());
-                        //     bullets.push(Bullet {
-                        //         pos: ship.pos + dir * (SHIP_HEIGHT / 2.0),
-                        //         vel: dir * 7.0,
-                        //         shot_at: now,
-                        //         collided: false,
-                        //     });
-                        //     last_shot = now;
-                        // }
+                        new_rank = None;
+                        audio.stop_loops();
+                        game_state = if high_scores.qualifies(world.player_score) {
+                            name_cursor = 0;
+                            // Letters typed while playing must not end up in the name.
+                            clear_input_queue();
+                            GameState::EnterName
+                        } else {
+                            GameState::GameOver
+                        };
+                        break;
+                    } else if world.is_cleared() {
+                        audio.level_clear();
+                        game_state = GameState::Win;
+                        break;
                     }
                 }
-
-                ship.vel += acc;
-                if ship.vel.length() > 5.0 {
-                    ship.vel = ship.vel.normalize() * 5.0;
-                }
-                ship.pos += ship.vel;
-                ship.pos = wrap_around(&ship.pos);
-
-                for b in bullets.iter_mut() {
-                    b.pos += b.vel;
+                if matches!(game_state, GameState::Playing) {
+                    let ship = &world.ship;
+                    audio.set_thrust(
+                        input.thrust > 0.0 && world.ship_lost_at.is_none() && ship.warp <= 0.0,
+                    );
+                    audio.heartbeat(frame_time, world.asteroids.len());
                 }
-                for a in asteroids.iter_mut() {
-                    a.pos += a.vel;
-                    a.pos = wrap_around(&a.pos);
-                    a.rot += a.rot_speed;
 
-                    let max_speed = 5.0 + (level_multiplier as f32) * 0.5;
-                    if a.vel.length() > max_speed {
-                        a.vel = a.vel.normalize() * max_speed;
-                    }
+                if world.spawn_in() >= 1.0 {
+                    fade_from = None;
                 }
+                display::begin_field(world.size());
+                draw_world(
+                    &world,
+                    &palettes[world.palette],
+                    fade_from.as_ref(),
+                    accumulator / TICK,
+                );
+                particles.update(frame_time, world.size());
+                particles.draw();
+                display::end_field(world.size());
 
-                bullets.retain(|b| b.shot_at + 1.5 > now && !b.collided);
+                draw_hud(&world);
 
-                let mut new_asts = Vec::new();
-                let mut collided_ship = false;
-                for a in asteroids.iter_mut() {
-                    if (a.pos - ship.pos).length() < a.size + SHIP_HEIGHT / 3.0 {
-                        collided_ship = true;
-                        break;
-                    }
-                    for b in bullets.iter_mut() {
-                        if (a.pos - b.pos).length() < a.size {
-                            a.collided = true;
-                            b.collided = true;
-                            let side_count = a.sides;
-                            player_score += side_count as u64 * SCORE_MULTIPLIER as u64;
-                            if side_count > 3 {
-                                new_asts.push(Asteroid {
-                                    pos: a.pos,
-                                    vel: vec2(b.vel.y, -b.vel.x).normalize()
-                                        * speed_factor(level_multiplier),
-                                    rot: rand::gen_range(0., 360.),
-                                    rot_speed: rand::gen_range(-2., 2.),
-                                    size: a.size * 0.8,
-                                    sides: a.sides - 1,
-                                    collided: false,
-                                    color: random_asteroid_color(current_palette),
-                                });
-                                new_asts.push(Asteroid {
-                                    pos: a.pos,
-                                    vel: vec2(-b.vel.y, b.vel.x).normalize()
-                                        * speed_factor(level_multiplier),
-                                    rot: rand::gen_range(0., 360.),
-                                    rot_speed: rand::gen_range(-2., 2.),
-                                    size: a.size * 0.8,
-                                    sides: a.sides - 1,
-                                    collided: false,
-                                    color: random_asteroid_color(current_palette),
-                                });
-                            }
-                            break;
-                        }
-                    }
+                if control_mode == ControlMode::Touch {
+                    touch.draw();
                 }
 
-                if collided_ship {
-                    game_state = GameState::GameOver;
-                } else {
-                    asteroids.retain(|a| !a.collided);
-                    asteroids.extend(new_asts);
-                    if asteroids.is_empty() {
-                        game_state = GameState::Win;
-                    }
-                }
-                let ang = ship.rot.to_radians();
-                let dir_f = vec2(ang.sin(), -ang.cos());
-                let dir_l = vec2(-ang.cos(), -ang.sin());
-                let dir_r = vec2(ang.cos(), ang.sin());
+                next_frame().await;
+            }
 
-                let nose = ship.pos + dir_f * (SHIP_HEIGHT * 0.8);
-                let back = ship.pos + dir_f * -(SHIP_HEIGHT * 0.3);
-                let half_base = SHIP_BASE * 0.3;
-                let v2 = back + dir_l * half_base;
-                let v3 = back + dir_r * half_base;
+            GameState::Paused => {
+                clear_background(LIGHTGRAY);
+                let base = screen_width().min(screen_height());
+                let fs = base * 0.05;
+                let fs2 = base * 0.035;
+                let rows = menu_rows(PAUSE_ITEMS.len());
 
-                // Forcing Base Color pallette for first level.
-                if level_multiplier == 1. {
-                    clear_background(LIGHTGRAY);
-                    for b in bullets.iter() {
-                        draw_circle(b.pos.x, b.pos.y, 2.0, BLACK);
-                    }
-                    for a in asteroids.iter() {
-                        draw_poly_lines(a.pos.x, a.pos.y, a.sides, a.size, a.rot, 2.0, BLACK);
-                    }
-                    draw_triangle_lines(nose, v2, v3, 2.0, BLACK);
+                if is_key_pressed(KeyCode::Up) || gamepads.nav_up() {
+                    pause_row = (pause_row + rows.len() - 1) % rows.len();
                 }
-                // Random color pallette per level
-                else {
-                    clear_background(current_palette.background);
-                    for b in bullets.iter() {
-                        draw_circle(b.pos.x, b.pos.y, 2.0, current_palette.ship);
-                    }
-                    for a in asteroids.iter() {
-                        draw_poly_lines(a.pos.x, a.pos.y, a.sides, a.size, a.rot, 2.0, a.color);
+                if is_key_pressed(KeyCode::Down) || gamepads.nav_down() {
+                    pause_row = (pause_row + 1) % rows.len();
+                }
+                let mut chosen = None;
+                if is_key_pressed(KeyCode::Enter) || gamepads.pressed(Button::South) {
+                    chosen = Some(PAUSE_ITEMS[pause_row]);
+                }
+                for (i, row) in rows.iter().enumerate() {
+                    if clicked(*row) {
+                        pause_row = i;
+                        chosen = Some(PAUSE_ITEMS[i]);
                     }
-                    draw_triangle_lines(nose, v2, v3, 2.0, current_palette.ship);
+                }
+                // Whatever paused the game also resumes it.
+                if bindings.pressed(Action::Pause)
+                    || gamepads.pressed(Button::Start)
+                    || gamepads.back()
+                {
+                    chosen = Some(PauseItem::Resume);
                 }
 
-                let base = screen_width().min(screen_height());
-                let score_and_level_fs = base * 0.04;
-                let alpha = 0.75;
-
-                // Drawing Score
-                let msg = player_score.to_string();
-                let ts = measure_text(msg.as_str(), None, score_and_level_fs as u16, 1.0);
-                draw_text(
-                    msg.as_str(),
-                    screen_width() / 4.0,
-                    ts.height * 2.0,
-                    score_and_level_fs,
-                    DARKGRAY.with_alpha(alpha),
-                );
-
-                // Drawing Level
-                let msg = format!("Level: {level_multiplier}");
-                let ts = measure_text(msg.as_str(), None, score_and_level_fs as u16, 1.0);
+                let title = "PAUSED";
+                let ts = measure_text(title, None, fs as u16, 1.0);
                 draw_text(
-                    msg.as_str(),
-                    screen_width() * 0.75,
-                    ts.height * 2.0,
-                    score_and_level_fs,
-                    DARKGRAY.with_alpha(alpha),
+                    title,
+                    screen_width() / 2.0 - ts.width / 2.0,
+                    screen_height() * 0.15,
+                    fs,
+                    DARKGRAY,
                 );
-
-                // Drawing touch controls
-                if control_mode == ControlMode::Touch {
-                    let scr_w = screen_width();
-                    let scr_h = screen_height();
-                    let btn_size = scr_w * 0.2;
-                    let rotation_btn_w = scr_w / 4.;
-
-                    let fs = screen_width().max(screen_height()) * 0.025;
-
-                    let left_btn = Rect::new(0.0, scr_h - btn_size, rotation_btn_w, btn_size);
-                    let right_btn =
-                        Rect::new(rotation_btn_w, scr_h - btn_size, rotation_btn_w, btn_size);
-                    let thrust_btn = Rect::new(scr_w / 2.0, scr_h - btn_size, scr_w / 2., btn_size);
-                    // let pause_btn = Rect::new(scr_w / 8., 0., scr_w / 6., btn_size);
-
-                    // let pause_ts = measure_text("PAUSE", None, fs as u16, 1.0);
-
-                    let alpha = 0.1;
-                    draw_rectangle(
-                        left_btn.x,
-                        left_btn.y,
-                        left_btn.w,
-                        left_btn.h,
-                        Color::new(0.0, 0.0, 0.0, alpha),
-                    );
-                    draw_rectangle(
-                        right_btn.x,
-                        right_btn.y,
-                        right_btn.w,
-                        right_btn.h,
-                        Color::new(0.0, 0.0, 0.0, alpha),
-                    );
-                    draw_rectangle(
-                        thrust_btn.x,
-                        thrust_btn.y,
-                        thrust_btn.w,
-                        thrust_btn.h,
-                        Color::new(0.0, 0.0, 0.0, alpha),
-                    );
-                    // draw_rectangle(
-                    //     pause_btn.x,
-                    //     pause_btn.y,
-                    //     pause_btn.w,
-                    //     pause_btn.h,
-                    //     Color::new(0.0, 0.0, 0.0, alpha),
-                    // );
-
-                    draw_rectangle_lines(
-                        left_btn.x, left_btn.y, left_btn.w, left_btn.h, 1.0, WHITE,
-                    );
-                    draw_rectangle_lines(
-                        right_btn.x,
-                        right_btn.y,
-                        right_btn.w,
-                        right_btn.h,
-                        1.0,
-                        WHITE,
-                    );
-                    draw_rectangle_lines(
-                        thrust_btn.x,
-                        thrust_btn.y,
-                        thrust_btn.w,
-                        thrust_btn.h,
-                        1.0,
-                        WHITE,
-                    );
-
-                    let small = btn_size * 0.3;
-                    draw_text(
-                        "<",
-                        left_btn.x + left_btn.w / 2.0 - small / 2.0,
-                        left_btn.y + left_btn.h / 2.0 + small / 2.0,
-                        small,
-                        WHITE,
-                    );
-                    draw_text(
-                        ">",
-                        right_btn.x + right_btn.w / 2.0 - small / 2.0,
-                        right_btn.y + right_btn.h / 2.0 + small / 2.0,
-                        small,
-                        WHITE,
-                    );
-                    draw_text(
-                        "^",
-                        thrust_btn.x + thrust_btn.w / 2.0 - small / 2.0,
-                        thrust_btn.y + thrust_btn.h / 2.0 + small / 2.0,
-                        small,
-                        WHITE,
-                    );
-                    // draw_text(
-                    //     "PAUSE",
-                    //     pause_btn.x + pause_btn.w / 2.0 - small / 2.0,
-                    //     pause_btn.y + pause_btn.h / 2.0 + small / 2.0,
-                    //     small,
-                    //     WHITE,
-                    // );
-
-                    let fire_label = "Tap anywhere to FIRE";
-                    let ts = measure_text(fire_label, None, fs as u16, 1.0);
+                for (i, (row, item)) in rows.iter().zip(PAUSE_ITEMS).enumerate() {
+                    let color = if i == pause_row { BLACK } else { DARKGRAY };
+                    draw_rectangle_lines(row.x, row.y, row.w, row.h, 2.0, color);
+                    draw_centered(item.label(), *row, fs, color);
+                }
+                if control_mode == ControlMode::Gamepad && !gamepads.connected() {
+                    let msg = "Controller disconnected - reconnect it or use the keyboard";
+                    let ts = measure_text(msg, None, fs2 as u16, 1.0);
                     draw_text(
-                        fire_label,
-                        scr_w / 2.0 - ts.width / 2.0,
-                        scr_h - btn_size - 12.0,
-                        fs,
-                        WHITE,
+                        msg,
+                        screen_width() / 2.0 - ts.width / 2.0,
+                        screen_height() * 0.92,
+                        fs2,
+                        DARKGRAY,
                     );
                 }
 
+                match chosen {
+                    Some(PauseItem::Resume) => game_state = GameState::Playing,
+                    Some(PauseItem::Restart) => {
+                        // Dropping the inputs played since keeps the replay
+                        // in step with the restored world.
+                        if let Some((start, inputs)) = &level_start {
+                            world = start.clone();
+                            recording.inputs.truncate(*inputs);
+                            recording.too_long = false;
+                        }
+                        particles.clear();
+                        accumulator = 0.0;
+                        game_state = GameState::Playing;
+                    }
+                    Some(PauseItem::Settings) => {
+                        settings_row = 0;
+                        game_state = GameState::Settings;
+                    }
+                    Some(PauseItem::MainMenu) => {
+                        // An abandoned run is neither scored nor saved as a
+                        // replay.
+                        world = World::new(fresh_seed(), &palettes, FIELD_WIDTH, FIELD_HEIGHT);
+                        particles.clear();
+                        level_start = None;
+                        fade_from = None;
+                        game_state = GameState::StartMenu;
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    Some(PauseItem::Quit) => game_state = GameState::Quit,
+                    None => {}
+                }
                 next_frame().await;
             }
 
-            GameState::Paused => {
+            GameState::Settings => {
                 clear_background(LIGHTGRAY);
                 let base = screen_width().min(screen_height());
                 let fs = base * 0.05;
-                let fs2 = base * 0.04;
-                let msg = "PAUSED";
-                let msg2 = "Press [enter] to Resume";
-                let ts = measure_text(msg, None, fs as u16, 1.0);
-                let ts2 = measure_text(msg2, None, fs2 as u16, 1.0);
+                let rows = menu_rows(SETTINGS_ITEMS.len());
+
+                if is_key_pressed(KeyCode::Up) || gamepads.nav_up() {
+                    settings_row = (settings_row + rows.len() - 1) % rows.len();
+                }
+                if is_key_pressed(KeyCode::Down) || gamepads.nav_down() {
+                    settings_row = (settings_row + 1) % rows.len();
+                }
+                let mut chosen = None;
+                if is_key_pressed(KeyCode::Enter) || gamepads.pressed(Button::South) {
+                    chosen = Some(settings_row);
+                }
+                for (i, row) in rows.iter().enumerate() {
+                    if clicked(*row) {
+                        settings_row = i;
+                        chosen = Some(i);
+                    }
+                }
+                if is_key_pressed(KeyCode::Escape) || gamepads.back() {
+                    chosen = Some(SETTINGS_ITEMS.len() - 1);
+                }
+
+                let title = "SETTINGS";
+                let ts = measure_text(title, None, fs as u16, 1.0);
                 draw_text(
-                    msg,
+                    title,
                     screen_width() / 2.0 - ts.width / 2.0,
-                    screen_height() / 2.0 - ts.height - 20.0,
+                    screen_height() * 0.15,
                     fs,
                     DARKGRAY,
                 );
-                draw_text(
-                    msg2,
-                    screen_width() / 2.0 - ts2.width / 2.0,
-                    screen_height() / 2.0 + 20.0,
-                    fs2,
-                    DARKGRAY,
-                );
-                if is_key_pressed(KeyCode::Enter) {
-                    game_state = GameState::Playing;
+                for (i, (row, label)) in rows.iter().zip(SETTINGS_ITEMS).enumerate() {
+                    let color = if i == settings_row { BLACK } else { DARKGRAY };
+                    let label = match i {
+                        4 => format!("{label}: {}", color_mode.label()),
+                        5 if fullscreen => format!("{label}: Fullscreen"),
+                        5 => format!("{label}: Windowed"),
+                        _ => label.to_string(),
+                    };
+                    draw_rectangle_lines(row.x, row.y, row.w, row.h, 2.0, color);
+                    draw_centered(&label, *row, fs, color);
+                }
+
+                match chosen {
+                    Some(0) => {
+                        sound_row = 0;
+                        settings_return = GameState::Settings;
+                        game_state = GameState::SoundSettings;
+                    }
+                    Some(1) => {
+                        controls_row = 0;
+                        controls_slot = 0;
+                        controls_msg.clear();
+                        confirm_reset = false;
+                        settings_return = GameState::Settings;
+                        game_state = GameState::Controls;
+                    }
+                    Some(2) => {
+                        touch_row = 0;
+                        settings_return = GameState::Settings;
+                        game_state = GameState::TouchSettings;
+                    }
+                    Some(3) => {
+                        let name = &palettes[world.palette].name;
+                        palette_view = all_palettes
+                            .iter()
+                            .position(|p| &p.name == name)
+                            .unwrap_or(0);
+                        game_state = GameState::Palettes;
+                    }
+                    Some(4) => {
+                        color_mode = color_mode.next();
+                        if let Err(e) = color_mode.save() {
+                            error!("{}", e);
+                        }
+                        // The level carries on in the same palette if the new
+                        // mode allows it, otherwise in one that it does.
+                        let name = palettes[world.palette].name.clone();
+                        palettes = usable_palettes(&all_palettes, color_mode);
+                        let keep = |w: &World| {
+                            palettes
+                                .iter()
+                                .position(|p| p.name == name)
+                                .unwrap_or(w.palette.min(palettes.len() - 1))
+                        };
+                        let index = keep(&world);
+                        world.set_palette(index, &palettes);
+                        if let Some((start, _)) = &mut level_start {
+                            start.set_palette(keep(start), &palettes);
+                        }
+                        fade_from = None;
+                    }
+                    Some(5) => {
+                        fullscreen = !fullscreen;
+                        set_fullscreen(fullscreen);
+                        if let Err(e) = display::save_fullscreen(fullscreen) {
+                            error!("{}", e);
+                        }
+                    }
+                    Some(_) => game_state = GameState::Paused,
+                    None => {}
                 }
                 next_frame().await;
             }
@@ -681,14 +1176,16 @@ async fn main() {
 
                 let fs = base * 0.05;
                 let fs2 = base * 0.04;
-                let msg = format!("GAME OVER -> FINAL SCORE: {player_score}");
-                let msg2 = if control_mode == ControlMode::Touch {
-                    "Tap to Restart"
-                } else {
-                    "Press [enter] to Restart"
+                let msg = format!("GAME OVER -> FINAL SCORE: {}", world.player_score);
+                let msg2 = match control_mode {
+                    ControlMode::Touch => "Tap to Restart",
+                    ControlMode::Keyboard => "Press [enter] to Restart",
+                    ControlMode::Gamepad => "Press [A] to Restart",
                 };
+                let msg3 = format!("Seed: {}", world.seed);
                 let ts = measure_text(msg.as_str(), None, fs as u16, 1.0);
                 let ts2 = measure_text(msg2, None, fs2 as u16, 1.0);
+                let ts3 = measure_text(msg3.as_str(), None, fs2 as u16, 1.0);
                 draw_text(
                     msg.as_str(),
                     screen_width() / 2.0 - ts.width / 2.0,
@@ -703,28 +1200,21 @@ async fn main() {
                     fs2,
                     DARKGRAY,
                 );
-                if is_key_pressed(KeyCode::Enter) {
-                    level_multiplier = 1.0;
-                    current_palette = pick_palette_for_level(level_multiplier, &palettes);
-                    let (ns, nb, na, nls, ps) = new_game(level_multiplier, current_palette);
-                    ship = ns;
-                    bullets = nb;
-                    asteroids = na;
-                    last_shot = nls;
+                draw_text(
+                    msg3.as_str(),
+                    screen_width() / 2.0 - ts3.width / 2.0,
+                    screen_height() / 2.0 + 20.0 + ts2.height * 2.0,
+                    fs2,
+                    DARKGRAY,
+                );
+                if is_key_pressed(KeyCode::Enter) || gamepads.confirm() {
+                    world = World::new(fresh_seed(), &palettes, FIELD_WIDTH, FIELD_HEIGHT);
                     game_state = GameState::StartMenu;
-                    player_score = ps;
                 } else {
                     for touch in touches() {
                         if touch.phase == TouchPhase::Started {
-                            level_multiplier = 1.0;
-                            current_palette = pick_palette_for_level(level_multiplier, &palettes);
-                            let (ns, nb, na, nls, ps) = new_game(level_multiplier, current_palette);
-                            ship = ns;
-                            bullets = nb;
-                            asteroids = na;
-                            last_shot = nls;
+                            world = World::new(fresh_seed(), &palettes, FIELD_WIDTH, FIELD_HEIGHT);
                             game_state = GameState::StartMenu;
-                            player_score = ps;
                             break;
                         }
                     }
@@ -733,14 +1223,99 @@ async fn main() {
                 next_frame().await;
             }
 
+            GameState::EnterName => {
+                clear_background(LIGHTGRAY);
+                let base = screen_width().min(screen_height());
+                let fs = base * 0.05;
+                let fs_letter = base * 0.1;
+
+                let step =
+                    |letter: u8, by: i8| (((letter - b'A') as i8 + by).rem_euclid(26) as u8) + b'A';
+                for c in typed_chars() {
+                    if c.is_ascii_alphabetic() {
+                        name[name_cursor] = c.to_ascii_uppercase() as u8;
+                        name_cursor = (name_cursor + 1).min(NAME_LEN - 1);
+                    }
+                }
+                if is_key_pressed(KeyCode::Up) || gamepads.nav_up() {
+                    name[name_cursor] = step(name[name_cursor], 1);
+                }
+                if is_key_pressed(KeyCode::Down) || gamepads.nav_down() {
+                    name[name_cursor] = step(name[name_cursor], -1);
+                }
+                if is_key_pressed(KeyCode::Left)
+                    || is_key_pressed(KeyCode::Backspace)
+                    || gamepads.nav_left()
+                    || gamepads.back()
+                {
+                    name_cursor = name_cursor.saturating_sub(1);
+                }
+                if is_key_pressed(KeyCode::Right) || gamepads.nav_right() {
+                    name_cursor = (name_cursor + 1).min(NAME_LEN - 1);
+                }
+
+                let title = format!("NEW HIGH SCORE: {}", world.player_score);
+                let ts = measure_text(title.as_str(), None, fs as u16, 1.0);
+                draw_text(
+                    title.as_str(),
+                    screen_width() / 2.0 - ts.width / 2.0,
+                    screen_height() * 0.15,
+                    fs,
+                    DARKGRAY,
+                );
+
+                for (i, (up, letter, down)) in name_slots().into_iter().enumerate() {
+                    if clicked(up) {
+                        name_cursor = i;
+                        name[i] = step(name[i], 1);
+                    } else if clicked(down) {
+                        name_cursor = i;
+                        name[i] = step(name[i], -1);
+                    } else if clicked(letter) {
+                        name_cursor = i;
+                    }
+                    let color = if i == name_cursor { BLACK } else { DARKGRAY };
+                    draw_centered("^", up, fs_letter, DARKGRAY);
+                    draw_centered("v", down, fs_letter, DARKGRAY);
+                    draw_rectangle_lines(letter.x, letter.y, letter.w, letter.h, 2.0, color);
+                    draw_centered(&(name[i] as char).to_string(), letter, fs_letter, color);
+                }
+
+                let ok = confirm_button();
+                draw_rectangle_lines(ok.x, ok.y, ok.w, ok.h, 2.0, DARKGRAY);
+                draw_centered("OK [Enter]", ok, fs, DARKGRAY);
+
+                // [A] moves on to the next letter, and accepts on the last one.
+                let pad_done = gamepads.pressed(Button::Start)
+                    || (gamepads.pressed(Button::South) && name_cursor == NAME_LEN - 1);
+                if gamepads.pressed(Button::South) {
+                    name_cursor = (name_cursor + 1).min(NAME_LEN - 1);
+                }
+                if is_key_pressed(KeyCode::Enter) || pad_done || clicked(ok) {
+                    new_rank = high_scores.insert(HighScore {
+                        name: String::from_utf8_lossy(&name).into_owned(),
+                        score: world.player_score,
+                        level: world.level_multiplier as u32,
+                        date: highscores::today(),
+                    });
+                    if let Err(e) = high_scores.save() {
+                        error!("saving high scores failed: {}", e);
+                    }
+                    game_state = GameState::GameOver;
+                }
+
+                next_frame().await;
+            }
+
             GameState::Win => {
-                clear_background(current_palette.background);
+                let palette = &palettes[world.palette];
+                clear_background(palette.background);
                 let base = screen_width().min(screen_height());
                 let fs = base * 0.05;
-                let msg = if control_mode == ControlMode::Touch {
-                    "You Win! Tap to Move To Next Level"
-                } else {
-                    "You Win! Press Enter to Move To Next Level"
+                let msg = match control_mode {
+                    ControlMode::Touch => "You Win! Tap to Move To Next Level",
+                    ControlMode::Keyboard => "You Win! Press Enter to Move To Next Level",
+                    ControlMode::Gamepad => "You Win! Press [A] to Move To Next Level",
                 };
                 let ts = measure_text(msg, None, fs as u16, 1.0);
                 draw_text(
@@ -748,27 +1323,21 @@ async fn main() {
                     screen_width() / 2.0 - ts.width / 2.0,
                     screen_height() / 2.0 - ts.height - 20.0,
                     fs,
-                    current_palette.ship,
+                    palette.ship,
                 );
-                if is_key_pressed(KeyCode::Enter) {
-                    level_multiplier += 1.0;
-                    current_palette = pick_palette_for_level(level_multiplier, &palettes);
-                    let (ns, nb, na, nls, _ps) = new_game(level_multiplier, current_palette);
-                    ship = ns;
-                    bullets = nb;
-                    asteroids = na;
-                    last_shot = nls;
+                if is_key_pressed(KeyCode::Enter) || gamepads.confirm() {
+                    fade_from = Some(palettes[world.palette].clone());
+                    world.next_level(&palettes);
+                    particles.clear();
+                    level_start = None;
                     game_state = GameState::Playing;
                 }
                 for touch in touches() {
                     if touch.phase == TouchPhase::Started {
-                        level_multiplier += 1.0;
-                        current_palette = pick_palette_for_level(level_multiplier, &palettes);
-                        let (ns, nb, na, nls, _ps) = new_game(level_multiplier, current_palette);
-                        ship = ns;
-                        bullets = nb;
-                        asteroids = na;
-                        last_shot = nls;
+                        fade_from = Some(palettes[world.palette].clone());
+                        world.next_level(&palettes);
+                        particles.clear();
+                        level_start = None;
                         game_state = GameState::Playing;
                         break;
                     }
@@ -777,30 +1346,30 @@ async fn main() {
             }
 
             GameState::InfoScreen => {
-                let curr_color = if level_multiplier == 1. {
-                    (LIGHTGRAY, DARKGRAY)
-                } else {
-                    (current_palette.background, current_palette.ship)
-                };
+                let palette = &palettes[world.palette];
+                let curr_color = (palette.background, palette.ship);
                 clear_background(curr_color.0);
                 let base = screen_width().min(screen_height());
                 let fs = base * 0.05;
                 let fs2 = base * 0.04;
-                let msg = if control_mode == ControlMode::Touch {
-                    "Move with [buttons] ship will autofire."
-                } else {
-                    "Move with [wasd] or [arrows] fire with [space]."
+                let msg = match control_mode {
+                    ControlMode::Touch => touch.settings.help().to_string(),
+                    ControlMode::Keyboard => bindings.help(),
+                    ControlMode::Gamepad => {
+                        "Steer with the stick, [A] to thrust, [RT] or [X] to fire, [B] to jump, [Y] to shield."
+                            .to_string()
+                    }
                 };
-                let msg2 = if control_mode == ControlMode::Touch {
-                    "[Tap Screen To Start Game]"
-                } else {
-                    "[Press Enter To Start Game]"
+                let msg2 = match control_mode {
+                    ControlMode::Touch => "[Tap Screen To Start Game]",
+                    ControlMode::Keyboard => "[Press Enter To Start Game]",
+                    ControlMode::Gamepad => "[Press Start To Start Game]",
                 };
-                let ts = measure_text(msg, None, fs as u16, 1.0);
+                let ts = measure_text(msg.as_str(), None, fs as u16, 1.0);
                 let ts2 = measure_text(msg2, None, fs2 as u16, 1.0);
 
                 draw_text(
-                    msg,
+                    msg.as_str(),
                     screen_width() / 2.0 - ts.width / 2.0,
                     screen_height() / 2.0 - ts.height - 20.0,
                     fs,
@@ -815,34 +1384,555 @@ async fn main() {
                 );
                 for touch in touches() {
                     if touch.phase == TouchPhase::Started {
-                        current_palette = pick_palette_for_level(level_multiplier, &palettes);
-                        let (ns, nb, na, nls, ps) = new_game(level_multiplier, current_palette);
-                        ship = ns;
-                        bullets = nb;
-                        asteroids = na;
-                        last_shot = nls;
+                        let seed = seed_entry.parse().unwrap_or_else(|_| fresh_seed());
+                        world = World::new(seed, &palettes, FIELD_WIDTH, FIELD_HEIGHT);
+                        world.asteroid_collisions = asteroid_collisions;
+                        world.set_extra_life_every(extra_life_every);
+                        particles.clear();
+                        level_start = None;
+                        fade_from = None;
+                        recording = Replay::new(&world);
                         control_mode = ControlMode::Touch;
                         game_state = GameState::Playing;
-                        player_score = ps;
                         break;
                     }
                 }
-                if is_key_pressed(KeyCode::Enter) {
-                    current_palette = pick_palette_for_level(level_multiplier, &palettes);
-                    let (ns, nb, na, nls, ps) = new_game(level_multiplier, current_palette);
-                    ship = ns;
-                    bullets = nb;
-                    asteroids = na;
-                    last_shot = nls;
-                    control_mode = ControlMode::Keyboard;
+                let keyboard = is_key_pressed(KeyCode::Enter);
+                if keyboard || gamepads.pressed(Button::Start) {
+                    let seed = seed_entry.parse().unwrap_or_else(|_| fresh_seed());
+                    world = World::new(seed, &palettes, FIELD_WIDTH, FIELD_HEIGHT);
+                    world.asteroid_collisions = asteroid_collisions;
+                    world.set_extra_life_every(extra_life_every);
+                    particles.clear();
+                    level_start = None;
+                    fade_from = None;
+                    recording = Replay::new(&world);
+                    control_mode = if keyboard {
+                        ControlMode::Keyboard
+                    } else {
+                        ControlMode::Gamepad
+                    };
                     game_state = GameState::Playing;
-                    player_score = ps;
                 }
                 next_frame().await;
             }
 
+            GameState::SoundSettings => {
+                clear_background(LIGHTGRAY);
+                let base = screen_width().min(screen_height());
+                let fs = base * 0.05;
+                let volume = &mut audio.volume;
+                let mut levels = [&mut volume.master, &mut volume.sfx, &mut volume.music];
+                let labels = ["Master", "Effects", "Music"];
+                let mut changed = false;
+
+                if is_key_pressed(KeyCode::Up) || gamepads.nav_up() {
+                    sound_row = (sound_row + levels.len() - 1) % levels.len();
+                }
+                if is_key_pressed(KeyCode::Down) || gamepads.nav_down() {
+                    sound_row = (sound_row + 1) % levels.len();
+                }
+                let mut adjust = |row: usize, by: f32| {
+                    *levels[row] = ((*levels[row] + by) * 10.0).round().clamp(0.0, 10.0) / 10.0;
+                    changed = true;
+                };
+                if is_key_pressed(KeyCode::Left) || gamepads.nav_left() {
+                    adjust(sound_row, -0.1);
+                }
+                if is_key_pressed(KeyCode::Right) || gamepads.nav_right() {
+                    adjust(sound_row, 0.1);
+                }
+                for (i, (minus, plus)) in volume_rows().into_iter().enumerate() {
+                    if clicked(minus) {
+                        sound_row = i;
+                        adjust(i, -0.1);
+                    } else if clicked(plus) {
+                        sound_row = i;
+                        adjust(i, 0.1);
+                    }
+                }
+
+                let title = "SOUND";
+                let ts = measure_text(title, None, fs as u16, 1.0);
+                draw_text(
+                    title,
+                    screen_width() / 2.0 - ts.width / 2.0,
+                    screen_height() * 0.15,
+                    fs,
+                    DARKGRAY,
+                );
+                for (i, (minus, plus)) in volume_rows().into_iter().enumerate() {
+                    let color = if i == sound_row { BLACK } else { DARKGRAY };
+                    let ts = measure_text(labels[i], None, fs as u16, 1.0);
+                    draw_text(
+                        labels[i],
+                        minus.x - ts.width - minus.w * 0.5,
+                        minus.y + minus.h / 2.0 + ts.height / 2.0,
+                        fs,
+                        color,
+                    );
+                    draw_rectangle_lines(minus.x, minus.y, minus.w, minus.h, 2.0, color);
+                    draw_centered("-", minus, fs, color);
+                    let value = Rect::new(
+                        minus.x + minus.w,
+                        minus.y,
+                        plus.x - minus.x - minus.w,
+                        minus.h,
+                    );
+                    let level = format!("{}%", (*levels[i] * 100.0).round());
+                    draw_centered(&level, value, fs, color);
+                    draw_rectangle_lines(plus.x, plus.y, plus.w, plus.h, 2.0, color);
+                    draw_centered("+", plus, fs, color);
+                }
+
+                let back = confirm_button();
+                draw_rectangle_lines(back.x, back.y, back.w, back.h, 2.0, DARKGRAY);
+                draw_centered("Back [Esc]", back, fs, DARKGRAY);
+
+                if changed {
+                    audio.apply_volume();
+                }
+                if is_key_pressed(KeyCode::Escape)
+                    || is_key_pressed(KeyCode::Enter)
+                    || gamepads.confirm()
+                    || gamepads.back()
+                    || clicked(back)
+                {
+                    if let Err(e) = audio.volume.save() {
+                        error!("saving volume failed: {}", e);
+                    }
+                    game_state = settings_return;
+                }
+
+                next_frame().await;
+            }
+
+            GameState::Controls => {
+                clear_background(LIGHTGRAY);
+                let base = screen_width().min(screen_height());
+                let fs = base * 0.04;
+                let slots = binding_slots();
+                let (reset, back) = controls_buttons();
+
+                if listening {
+                    if let Some(key) = get_last_key_pressed() {
+                        listening = false;
+                        controls_conflict = false;
+                        let action = ACTIONS[controls_row];
+                        controls_msg = if key == KeyCode::Escape {
+                            String::new()
+                        } else if !is_bindable(key) {
+                            format!("[{}] can't be bound", key_name(key))
+                        } else {
+                            match bindings.bind(action, controls_slot, key) {
+                                Ok(()) => String::new(),
+                                Err(other) => {
+                                    controls_conflict = true;
+                                    format!(
+                                        "[{}] is already bound to {} - clear it there first",
+                                        key_name(key),
+                                        other.label()
+                                    )
+                                }
+                            }
+                        };
+                    }
+                } else {
+                    if is_key_pressed(KeyCode::Up) || gamepads.nav_up() {
+                        controls_row = (controls_row + ACTIONS.len() - 1) % ACTIONS.len();
+                    }
+                    if is_key_pressed(KeyCode::Down) || gamepads.nav_down() {
+                        controls_row = (controls_row + 1) % ACTIONS.len();
+                    }
+                    if is_key_pressed(KeyCode::Left) || gamepads.nav_left() {
+                        controls_slot = 0;
+                    }
+                    if is_key_pressed(KeyCode::Right) || gamepads.nav_right() {
+                        controls_slot = SLOTS - 1;
+                    }
+                    let mut start = is_key_pressed(KeyCode::Enter);
+                    for (row, row_slots) in slots.iter().enumerate() {
+                        for (slot, rect) in row_slots.iter().enumerate() {
+                            if clicked(*rect) {
+                                controls_row = row;
+                                controls_slot = slot;
+                                start = true;
+                            }
+                        }
+                    }
+                    let reset_pressed = is_key_pressed(KeyCode::R) || clicked(reset);
+                    if !reset_pressed && (start || get_last_key_pressed().is_some()) {
+                        confirm_reset = false;
+                    }
+                    if start {
+                        listening = true;
+                        controls_conflict = false;
+                        controls_msg = format!(
+                            "Press a key for {} ([Esc] cancels)",
+                            ACTIONS[controls_row].label()
+                        );
+                    } else if is_key_pressed(KeyCode::Backspace) || is_key_pressed(KeyCode::Delete)
+                    {
+                        match bindings.clear(ACTIONS[controls_row], controls_slot) {
+                            Ok(()) => {
+                                controls_msg.clear();
+                                controls_conflict = false;
+                            }
+                            Err(e) => {
+                                controls_msg = e;
+                                controls_conflict = true;
+                            }
+                        }
+                    } else if reset_pressed && !confirm_reset {
+                        confirm_reset = true;
+                        controls_msg = "Press [R] or Reset again to reset every key".to_string();
+                        controls_conflict = true;
+                    } else if reset_pressed {
+                        confirm_reset = false;
+                        bindings = Bindings::default();
+                        controls_msg = "Controls reset to defaults".to_string();
+                        controls_conflict = false;
+                    } else if is_key_pressed(KeyCode::Escape) || gamepads.back() || clicked(back) {
+                        if let Err(e) = bindings.save() {
+                            error!("saving controls failed: {}", e);
+                        }
+                        game_state = settings_return;
+                    }
+                }
+
+                let title = "CONTROLS";
+                let ts = measure_text(title, None, (fs * 1.25) as u16, 1.0);
+                draw_text(
+                    title,
+                    screen_width() / 2.0 - ts.width / 2.0,
+                    screen_height() * 0.12,
+                    fs * 1.25,
+                    DARKGRAY,
+                );
+                for (row, action) in ACTIONS.into_iter().enumerate() {
+                    let keys = bindings.keys(action);
+                    let label_color = if row == controls_row { BLACK } else { DARKGRAY };
+                    let first = slots[row][0];
+                    let ts = measure_text(action.label(), None, fs as u16, 1.0);
+                    draw_text(
+                        action.label(),
+                        first.x - ts.width - first.h * 0.5,
+                        first.y + first.h / 2.0 + ts.height / 2.0,
+                        fs,
+                        label_color,
+                    );
+                    for (slot, rect) in slots[row].iter().enumerate() {
+                        let selected = row == controls_row && slot == controls_slot;
+                        let color = if selected { BLACK } else { DARKGRAY };
+                        let thickness = if selected { 3.0 } else { 1.0 };
+                        draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, thickness, color);
+                        let name = if selected && listening {
+                            "...".to_string()
+                        } else {
+                            keys[slot].map_or("-".to_string(), key_name)
+                        };
+                        draw_centered(&name, *rect, fs, color);
+                    }
+                }
+                let help = "[Enter] rebind, [Backspace] clear, [R] reset, [Esc] back";
+                let msg = if controls_msg.is_empty() {
+                    help
+                } else {
+                    controls_msg.as_str()
+                };
+                let ts = measure_text(msg, None, fs as u16, 1.0);
+                draw_text(
+                    msg,
+                    screen_width() / 2.0 - ts.width / 2.0,
+                    screen_height() * 0.71,
+                    fs,
+                    if controls_conflict { RED } else { DARKGRAY },
+                );
+                draw_rectangle_lines(reset.x, reset.y, reset.w, reset.h, 2.0, DARKGRAY);
+                draw_centered("Reset", reset, fs, DARKGRAY);
+                draw_rectangle_lines(back.x, back.y, back.w, back.h, 2.0, DARKGRAY);
+                draw_centered("Back", back, fs, DARKGRAY);
+
+                next_frame().await;
+            }
+
+            GameState::TouchSettings => {
+                clear_background(LIGHTGRAY);
+                let base = screen_width().min(screen_height());
+                let fs = base * 0.05;
+                let rows = touch_rows();
+                let back = confirm_button();
+
+                if is_key_pressed(KeyCode::Up) || gamepads.nav_up() {
+                    touch_row = (touch_row + rows.len() - 1) % rows.len();
+                }
+                if is_key_pressed(KeyCode::Down) || gamepads.nav_down() {
+                    touch_row = (touch_row + 1) % rows.len();
+                }
+                let mut toggle = None;
+                if is_key_pressed(KeyCode::Left)
+                    || is_key_pressed(KeyCode::Right)
+                    || is_key_pressed(KeyCode::Enter)
+                    || gamepads.nav_left()
+                    || gamepads.nav_right()
+                    || gamepads.pressed(Button::South)
+                {
+                    toggle = Some(touch_row);
+                }
+                for (i, row) in rows.iter().enumerate() {
+                    if clicked(*row) {
+                        touch_row = i;
+                        toggle = Some(i);
+                    }
+                }
+                let settings = &mut touch.settings;
+                match toggle {
+                    Some(0) => {
+                        settings.scheme = match settings.scheme {
+                            TouchScheme::Buttons => TouchScheme::Joystick,
+                            TouchScheme::Joystick => TouchScheme::Buttons,
+                        }
+                    }
+                    Some(_) => {
+                        settings.fire = match settings.fire {
+                            TouchFire::Auto => TouchFire::Tap,
+                            TouchFire::Tap => TouchFire::Auto,
+                        }
+                    }
+                    None => {}
+                }
+
+                let title = "TOUCH CONTROLS";
+                let ts = measure_text(title, None, fs as u16, 1.0);
+                draw_text(
+                    title,
+                    screen_width() / 2.0 - ts.width / 2.0,
+                    screen_height() * 0.15,
+                    fs,
+                    DARKGRAY,
+                );
+                let labels = [
+                    match settings.scheme {
+                        TouchScheme::Buttons => "Layout: Buttons",
+                        TouchScheme::Joystick => "Layout: Joystick",
+                    },
+                    match settings.fire {
+                        TouchFire::Auto => "Fire: Automatic",
+                        TouchFire::Tap => "Fire: Tap",
+                    },
+                ];
+                for (i, row) in rows.iter().enumerate() {
+                    let color = if i == touch_row { BLACK } else { DARKGRAY };
+                    draw_rectangle_lines(row.x, row.y, row.w, row.h, 2.0, color);
+                    draw_centered(labels[i], *row, fs, color);
+                }
+                let help = settings.help();
+                let ts = measure_text(help, None, (fs * 0.7) as u16, 1.0);
+                draw_text(
+                    help,
+                    screen_width() / 2.0 - ts.width / 2.0,
+                    screen_height() * 0.65,
+                    fs * 0.7,
+                    DARKGRAY,
+                );
+                draw_rectangle_lines(back.x, back.y, back.w, back.h, 2.0, DARKGRAY);
+                draw_centered("Back [Esc]", back, fs, DARKGRAY);
+
+                if is_key_pressed(KeyCode::Escape) || gamepads.back() || clicked(back) {
+                    if let Err(e) = touch.settings.save() {
+                        error!("saving touch settings failed: {}", e);
+                    }
+                    game_state = settings_return;
+                }
+
+                next_frame().await;
+            }
+
+            GameState::Palettes => {
+                let palette = &all_palettes[palette_view];
+                clear_background(palette.background);
+                let base = screen_width().min(screen_height());
+                let fs = base * 0.05;
+                let fs2 = base * 0.035;
+                let back = confirm_button();
+
+                if is_key_pressed(KeyCode::Left) || gamepads.nav_left() {
+                    palette_view = (palette_view + all_palettes.len() - 1) % all_palettes.len();
+                }
+                if is_key_pressed(KeyCode::Right)
+                    || gamepads.nav_right()
+                    || clicked(Rect::new(0.0, 0.0, screen_width(), back.y))
+                {
+                    palette_view = (palette_view + 1) % all_palettes.len();
+                }
+                if is_key_pressed(KeyCode::Escape)
+                    || is_key_pressed(KeyCode::Enter)
+                    || gamepads.back()
+                    || gamepads.confirm()
+                    || clicked(back)
+                {
+                    game_state = GameState::Settings;
+                }
+
+                let title = format!(
+                    "{} ({}/{})",
+                    palette.name,
+                    palette_view + 1,
+                    all_palettes.len()
+                );
+                let ts = measure_text(&title, None, fs as u16, 1.0);
+                draw_text(
+                    &title,
+                    screen_width() / 2.0 - ts.width / 2.0,
+                    screen_height() * 0.15,
+                    fs,
+                    palette.ship,
+                );
+
+                // A ship among one asteroid of each colour.
+                let [nose, v2, v3] =
+                    ship_vertices(vec2(screen_width() / 2.0, screen_height() * 0.3), 0.0);
+                draw_triangle_lines(nose, v2, v3, 2.0, palette.ship);
+                let count = palette.asteroid_colors.len();
+                let size = (screen_width() * 0.8 / count as f32 / 2.5).min(base * 0.06);
+                for (i, color) in palette.asteroid_colors.iter().enumerate() {
+                    let x = screen_width() * (0.1 + 0.8 * (i as f32 + 0.5) / count as f32);
+                    let sides = 3 + (i % 5) as u8;
+                    draw_poly_lines(x, screen_height() * 0.48, sides, size, 0.0, 2.0, *color);
+                }
+
+                // Whether levels can use it in the current colour mode.
+                let (check, check_color) = match color_mode.check(palette) {
+                    Ok(()) => (format!("Used in {} mode", color_mode.label()), palette.ship),
+                    Err(e) => (format!("Skipped in {} mode: {e}", color_mode.label()), RED),
+                };
+                let ts = measure_text(&check, None, fs2 as u16, 1.0);
+                draw_text(
+                    &check,
+                    screen_width() / 2.0 - ts.width / 2.0,
+                    screen_height() * 0.56,
+                    fs2,
+                    check_color,
+                );
+
+                let source = if palette_error.is_some() {
+                    "Built-in palettes - your theme file has a problem:".to_string()
+                } else if custom_theme {
+                    format!("Palettes from your {PALETTES_KEY}")
+                } else {
+                    format!("Built-in palettes - save a {PALETTES_KEY} to use your own")
+                };
+                let ts = measure_text(&source, None, fs2 as u16, 1.0);
+                draw_text(
+                    &source,
+                    screen_width() / 2.0 - ts.width / 2.0,
+                    screen_height() * 0.62,
+                    fs2,
+                    palette.ship,
+                );
+                if let Some(e) = &palette_error {
+                    let ts = measure_text(e, None, fs2 as u16, 1.0);
+                    draw_text(
+                        e,
+                        screen_width() / 2.0 - ts.width / 2.0,
+                        screen_height() * 0.62 + fs2 * 1.3,
+                        fs2,
+                        RED,
+                    );
+                }
+                let help = "[Left]/[Right] or tap to browse";
+                let ts = measure_text(help, None, fs2 as u16, 1.0);
+                draw_text(
+                    help,
+                    screen_width() / 2.0 - ts.width / 2.0,
+                    back.y - fs2,
+                    fs2,
+                    palette.ship,
+                );
+                draw_rectangle_lines(back.x, back.y, back.w, back.h, 2.0, palette.ship);
+                draw_centered("Back", back, fs, palette.ship);
+
+                next_frame().await;
+            }
+
+            GameState::Replay => {
+                let Some(replay) = &last_replay else {
+                    game_state = GameState::StartMenu;
+                    continue;
+                };
+
+                let mut finished = world.game_over || replay_tick >= replay.inputs.len();
+                let frame_time = if finished {
+                    0.0
+                } else {
+                    get_frame_time().min(MAX_FRAME_TIME)
+                };
+                accumulator += frame_time;
+                while !finished && accumulator >= TICK {
+                    let level_palette = world.palette;
+                    finished = !replay.play_tick(&mut world, replay_tick, &palettes);
+                    if world.palette != level_palette {
+                        fade_from = Some(palettes[level_palette].clone());
+                    }
+                    replay_tick += 1;
+                    accumulator -= TICK;
+                    for event in &world.events {
+                        audio.play(event);
+                        spawn_particles(&mut particles, event, &palettes[world.palette]);
+                    }
+                }
+
+                if world.spawn_in() >= 1.0 {
+                    fade_from = None;
+                }
+                display::begin_field(world.size());
+                draw_world(
+                    &world,
+                    &palettes[world.palette],
+                    fade_from.as_ref(),
+                    accumulator / TICK,
+                );
+                particles.update(frame_time, world.size());
+                particles.draw();
+                display::end_field(world.size());
+                draw_hud(&world);
+
+                let base = screen_width().min(screen_height());
+                let fs = base * 0.04;
+                let msg = if !finished {
+                    "REPLAY - press [Esc] or tap to exit".to_string()
+                } else if world.player_score == replay.final_score {
+                    format!("Replay verified: score {} matches", world.player_score)
+                } else {
+                    format!(
+                        "Replay diverged: score {}, recorded {}",
+                        world.player_score, replay.final_score
+                    )
+                };
+                let ts = measure_text(msg.as_str(), None, fs as u16, 1.0);
+                draw_text(
+                    msg.as_str(),
+                    screen_width() / 2.0 - ts.width / 2.0,
+                    screen_height() - ts.height * 2.0,
+                    fs,
+                    DARKGRAY,
+                );
+
+                let tapped = touches().iter().any(|t| t.phase == TouchPhase::Started);
+                if is_key_pressed(KeyCode::Escape)
+                    || gamepads.back()
+                    || tapped
+                    || (finished && (is_key_pressed(KeyCode::Enter) || gamepads.confirm()))
+                {
+                    world = World::new(fresh_seed(), &palettes, FIELD_WIDTH, FIELD_HEIGHT);
+                    game_state = GameState::StartMenu;
+                }
+                next_frame().await;
+            }
+
+            #[cfg(not(target_arch = "wasm32"))]
             GameState::Quit => {
-                window::order_quit();
+                miniquad::window::order_quit();
                 break;
             }
         }
@@ -0,0 +1,371 @@
+// Palettes come from a JSON theme file: the one built into the game, or a
+// `palettes.json` in storage that replaces it. Palette 0 is the level 1 look.
+// A palette can name the one that follows it to fix the order levels go
+// through; otherwise the next is picked from the seed.
+use macroquad::prelude::*;
+use nanoserde::DeJson;
+
+use crate::rng::Rng;
+
+pub const PALETTES_KEY: &str = "palettes.json";
+const BUILT_IN: &str = include_str!("../assets/palettes.json");
+const COLOR_MODE_KEY: &str = "color_mode.txt";
+
+#[derive(Clone)]
+pub struct ColorPalette {
+    pub name: String,
+    pub background: Color,
+    pub ship: Color,
+    pub asteroid_colors: Vec<Color>,
+    pub next: Option<String>,
+}
+
+#[derive(DeJson)]
+struct ThemeFile {
+    palettes: Vec<PaletteEntry>,
+}
+
+#[derive(DeJson)]
+struct PaletteEntry {
+    name: String,
+    background: String,
+    ship: String,
+    asteroids: Vec<String>,
+    // Empty when the next palette is left to the seed.
+    #[nserde(default)]
+    next: String,
+}
+
+// The user's theme if there is a valid one, otherwise the built-in palettes.
+// The error explains why a user theme was ignored.
+pub fn load_palettes() -> (Vec<ColorPalette>, Option<String>) {
+    let error = match crate::storage::load(PALETTES_KEY).map(|text| parse_palettes(&text)) {
+        Some(Ok(palettes)) => return (palettes, None),
+        Some(Err(e)) => Some(format!("{PALETTES_KEY}: {e}")),
+        None => None,
+    };
+    let palettes = parse_palettes(BUILT_IN).expect("built-in palettes are valid");
+    (palettes, error)
+}
+
+pub fn parse_palettes(text: &str) -> Result<Vec<ColorPalette>, String> {
+    let file = ThemeFile::deserialize_json(text).map_err(|e| e.to_string())?;
+    if file.palettes.is_empty() {
+        return Err("no palettes defined".to_string());
+    }
+    let names: Vec<&String> = file.palettes.iter().map(|p| &p.name).collect();
+    file.palettes
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let at = |what: &str| format!("palette {} ({:?}) {what}", i + 1, entry.name);
+            if entry.asteroids.is_empty() {
+                return Err(at("has no asteroid colours"));
+            }
+            let next = Some(&entry.next).filter(|n| !n.is_empty());
+            if let Some(next) = next {
+                if !names.contains(&next) {
+                    return Err(at(&format!("is followed by an unknown palette {next:?}")));
+                }
+                if *next == entry.name {
+                    return Err(at("is followed by itself"));
+                }
+            }
+            let color = |hex: &String| {
+                parse_color(hex)
+                    .ok_or_else(|| at(&format!("has a bad colour {hex:?}, expected #RRGGBB")))
+            };
+            Ok(ColorPalette {
+                background: color(&entry.background)?,
+                ship: color(&entry.ship)?,
+                asteroid_colors: entry
+                    .asteroids
+                    .iter()
+                    .map(color)
+                    .collect::<Result<_, _>>()?,
+                name: entry.name.clone(),
+                next: next.cloned(),
+            })
+        })
+        .collect()
+}
+
+// `#RRGGBB`, or `#RRGGBBAA` with alpha.
+fn parse_color(hex: &str) -> Option<Color> {
+    let digits = hex.strip_prefix('#')?;
+    if !matches!(digits.len(), 6 | 8) || !digits.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(digits.get(i..i + 2).unwrap_or("ff"), 16).ok();
+    Some(Color::from_rgba(
+        channel(0)?,
+        channel(2)?,
+        channel(4)?,
+        channel(6)?,
+    ))
+}
+
+// Accessibility modes. Each only allows palettes whose ship and asteroids
+// stand out from the background by its minimum contrast ratio, as seen with
+// the colour vision deficiency it is named for.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    Standard,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+    HighContrast,
+}
+
+pub const COLOR_MODES: [ColorMode; 5] = [
+    ColorMode::Standard,
+    ColorMode::Deuteranopia,
+    ColorMode::Protanopia,
+    ColorMode::Tritanopia,
+    ColorMode::HighContrast,
+];
+
+impl ColorMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            ColorMode::Standard => "Standard",
+            ColorMode::Deuteranopia => "Deuteranopia",
+            ColorMode::Protanopia => "Protanopia",
+            ColorMode::Tritanopia => "Tritanopia",
+            ColorMode::HighContrast => "High contrast",
+        }
+    }
+
+    pub fn next(self) -> ColorMode {
+        COLOR_MODES[(self as usize + 1) % COLOR_MODES.len()]
+    }
+
+    // 3:1 is the WCAG minimum for graphics. Without hue to tell objects
+    // apart the colour blind modes ask for the 4.5:1 text level, and high
+    // contrast for the 7:1 enhanced level.
+    fn min_contrast(self) -> f32 {
+        match self {
+            ColorMode::Standard => 3.0,
+            ColorMode::Deuteranopia | ColorMode::Protanopia | ColorMode::Tritanopia => 4.5,
+            ColorMode::HighContrast => 7.0,
+        }
+    }
+
+    // Full severity simulation matrices from Machado, Oliveira and Fernandes
+    // (2009), applied to linear RGB.
+    fn simulation(self) -> Option<[[f32; 3]; 3]> {
+        match self {
+            ColorMode::Deuteranopia => Some([
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ]),
+            ColorMode::Protanopia => Some([
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ]),
+            ColorMode::Tritanopia => Some([
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ]),
+            ColorMode::Standard | ColorMode::HighContrast => None,
+        }
+    }
+
+    pub fn load() -> ColorMode {
+        let text = crate::storage::load(COLOR_MODE_KEY).unwrap_or_default();
+        COLOR_MODES
+            .into_iter()
+            .find(|m| m.key() == text.trim())
+            .unwrap_or(ColorMode::Standard)
+    }
+
+    pub fn save(self) -> Result<(), String> {
+        crate::storage::save(COLOR_MODE_KEY, self.key())
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            ColorMode::Standard => "standard",
+            ColorMode::Deuteranopia => "deuteranopia",
+            ColorMode::Protanopia => "protanopia",
+            ColorMode::Tritanopia => "tritanopia",
+            ColorMode::HighContrast => "high-contrast",
+        }
+    }
+
+    // Relative luminance of `color` as someone the mode is for sees it.
+    fn luminance(self, color: Color) -> f32 {
+        let linear = |c: f32| {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        let mut rgb = [linear(color.r), linear(color.g), linear(color.b)];
+        if let Some(m) = self.simulation() {
+            rgb =
+                m.map(|row| (row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2]).clamp(0.0, 1.0));
+        }
+        0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2]
+    }
+
+    // WCAG contrast ratio between two colours, from 1:1 up to 21:1.
+    pub fn contrast_ratio(self, a: Color, b: Color) -> f32 {
+        let (la, lb) = (self.luminance(a), self.luminance(b));
+        (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+    }
+
+    // Why `palette` is not usable in this mode, if it is not.
+    pub fn check(self, palette: &ColorPalette) -> Result<(), String> {
+        let min = self.min_contrast();
+        let colors = std::iter::once(("ship", 0, palette.ship)).chain(
+            palette
+                .asteroid_colors
+                .iter()
+                .enumerate()
+                .map(|(i, c)| ("asteroid", i + 1, *c)),
+        );
+        for (what, n, color) in colors {
+            let ratio = self.contrast_ratio(palette.background, color);
+            if ratio < min {
+                let what = if n == 0 {
+                    what.to_string()
+                } else {
+                    format!("{what} colour {n}")
+                };
+                return Err(format!(
+                    "{what} is {ratio:.1}:1 against the background, needs {min}:1"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// The palettes usable in `mode`, in theme order. Level 1 gets the first of
+// them, and if none pass it gets the built-in classic look.
+pub fn usable_palettes(all: &[ColorPalette], mode: ColorMode) -> Vec<ColorPalette> {
+    let usable: Vec<ColorPalette> = all
+        .iter()
+        .filter(|p| mode.check(p).is_ok())
+        .cloned()
+        .collect();
+    if usable.is_empty() {
+        let built_in = parse_palettes(BUILT_IN).expect("built-in palettes are valid");
+        return built_in.into_iter().take(1).collect();
+    }
+    usable
+}
+
+impl ColorPalette {
+    // The palette `t` of the way from `self` to `to`, with `to`'s asteroid
+    // colours each fading in from the colour at the same place in `self`.
+    pub fn blend(&self, to: &ColorPalette, t: f32) -> ColorPalette {
+        let mix = |a: Color, b: Color| {
+            Color::new(
+                a.r + (b.r - a.r) * t,
+                a.g + (b.g - a.g) * t,
+                a.b + (b.b - a.b) * t,
+                a.a + (b.a - a.a) * t,
+            )
+        };
+        let from_colors = &self.asteroid_colors;
+        ColorPalette {
+            name: to.name.clone(),
+            background: mix(self.background, to.background),
+            ship: mix(self.ship, to.ship),
+            asteroid_colors: to
+                .asteroid_colors
+                .iter()
+                .enumerate()
+                .map(|(i, c)| mix(from_colors[i % from_colors.len()], *c))
+                .collect(),
+            next: to.next.clone(),
+        }
+    }
+}
+
+// Returns the index of the palette to draw `level` with, following on from
+// `current`. Level 1 is always palette 0. After that a palette's `next` is
+// used if it is in the list, otherwise any palette but the current one.
+pub fn pick_palette_for_level(
+    level: f64,
+    current: usize,
+    palettes: &[ColorPalette],
+    rng: &mut Rng,
+) -> usize {
+    if (level - 1.0).abs() < f64::EPSILON {
+        return 0;
+    }
+    // Always one draw, so a replay plays the same under any theme.
+    let roll: f64 = rng.gen_range(0.0, 1.0);
+    let current = current.min(palettes.len() - 1);
+    let next = palettes[current]
+        .next
+        .as_ref()
+        .and_then(|name| palettes.iter().position(|p| &p.name == name));
+    match next {
+        Some(next) => next,
+        None if palettes.len() == 1 => 0,
+        None => (current + 1 + (roll * (palettes.len() - 1) as f64) as usize) % palettes.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn palette(background: &str, ship: &str) -> ColorPalette {
+        ColorPalette {
+            name: "Test".to_string(),
+            background: parse_color(background).unwrap(),
+            ship: parse_color(ship).unwrap(),
+            asteroid_colors: vec![parse_color(ship).unwrap()],
+            next: None,
+        }
+    }
+
+    #[test]
+    fn black_on_white_is_21_to_1() {
+        let ratio = ColorMode::Standard.contrast_ratio(BLACK, WHITE);
+        assert!((ratio - 21.0).abs() < 0.01, "{ratio}");
+    }
+
+    #[test]
+    fn each_mode_rejects_a_palette_too_faint_for_it() {
+        assert!(
+            ColorMode::Standard
+                .check(&palette("#000000", "#404040"))
+                .is_err()
+        );
+        assert!(
+            ColorMode::HighContrast
+                .check(&palette("#000000", "#808080"))
+                .is_err()
+        );
+        // These pass 4.5:1 for normal sight and only fail once the
+        // deficiency is simulated.
+        for (mode, background, ship) in [
+            (ColorMode::Deuteranopia, "#00FFFF", "#8000FF"),
+            (ColorMode::Protanopia, "#000000", "#FF0000"),
+            (ColorMode::Tritanopia, "#0000FF", "#00FF00"),
+        ] {
+            let p = palette(background, ship);
+            assert!(ColorMode::Standard.contrast_ratio(p.background, p.ship) >= 4.5);
+            assert!(mode.check(&p).is_err(), "{}", mode.label());
+        }
+    }
+
+    #[test]
+    fn built_in_palettes_pass_the_standard_check() {
+        for palette in parse_palettes(BUILT_IN).unwrap() {
+            if let Err(e) = ColorMode::Standard.check(&palette) {
+                panic!("{}: {e}", palette.name);
+            }
+        }
+    }
+}
@@ -0,0 +1,125 @@
+// Short-lived line particles for debris, wreckage and sparks. They are purely
+// visual, so they live outside `World` and never touch its rng. The pool is
+// allocated once and new particles are dropped when it is full, which keeps
+// the cost flat on slow devices.
+use macroquad::color::Color;
+use macroquad::math::{Vec2, vec2};
+use macroquad::shapes::draw_line;
+
+use crate::rng::Rng;
+
+const CAPACITY: usize = 512;
+const DEBRIS_LIFE: f32 = 0.9;
+const WRECKAGE_LIFE: f32 = 2.0;
+const SPARK_LIFE: f32 = 0.3;
+const SPARKS_PER_IMPACT: usize = 6;
+const DEBRIS_SPEED: f32 = 70.0;
+const SPARK_SPEED: f32 = 160.0;
+
+struct Particle {
+    pos: Vec2,
+    vel: Vec2,
+    // Angle of the segment in radians and how fast it turns.
+    rot: f32,
+    spin: f32,
+    half_len: f32,
+    life: f32,
+    max_life: f32,
+    color: Color,
+}
+
+pub struct Particles {
+    pool: Vec<Particle>,
+    rng: Rng,
+}
+
+impl Particles {
+    pub fn new() -> Particles {
+        Particles {
+            pool: Vec::with_capacity(CAPACITY),
+            rng: Rng::new(0),
+        }
+    }
+
+    fn spawn(&mut self, particle: Particle) {
+        if self.pool.len() < CAPACITY {
+            self.pool.push(particle);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.pool.clear();
+    }
+
+    // Moves everything on by `dt`, wrapping inside a `size` playfield.
+    pub fn update(&mut self, dt: f32, size: Vec2) {
+        for p in self.pool.iter_mut() {
+            p.life -= dt;
+            p.pos += p.vel * dt;
+            p.pos = vec2(p.pos.x.rem_euclid(size.x), p.pos.y.rem_euclid(size.y));
+            p.rot += p.spin * dt;
+        }
+        self.pool.retain(|p| p.life > 0.0);
+    }
+
+    pub fn draw(&self) {
+        for p in self.pool.iter() {
+            let half = Vec2::from_angle(p.rot) * p.half_len;
+            let (a, b) = (p.pos - half, p.pos + half);
+            let mut color = p.color;
+            color.a *= p.life / p.max_life;
+            draw_line(a.x, a.y, b.x, b.y, 1.5, color);
+        }
+    }
+
+    // Breaks a closed outline into its edges, flung outward from the centre
+    // on top of the object's own velocity.
+    fn shatter(&mut self, outline: &[Vec2], vel: Vec2, life: f32, color: Color) {
+        let center = outline.iter().copied().sum::<Vec2>() / outline.len() as f32;
+        for i in 0..outline.len() {
+            let (a, b) = (outline[i], outline[(i + 1) % outline.len()]);
+            let mid = (a + b) / 2.0;
+            let edge = b - a;
+            let speed = DEBRIS_SPEED * self.rng.gen_range(0.5, 1.5);
+            let life = life * self.rng.gen_range(0.6, 1.0);
+            let spin = self.rng.gen_range(-6.0, 6.0);
+            self.spawn(Particle {
+                pos: mid,
+                vel: vel * 0.5 + (mid - center).normalize_or_zero() * speed,
+                rot: edge.y.atan2(edge.x),
+                spin,
+                half_len: edge.length() / 2.0,
+                life,
+                max_life: life,
+                color,
+            });
+        }
+    }
+
+    pub fn debris(&mut self, outline: &[Vec2], vel: Vec2, color: Color) {
+        self.shatter(outline, vel, DEBRIS_LIFE, color);
+    }
+
+    pub fn wreckage(&mut self, outline: &[Vec2], vel: Vec2, color: Color) {
+        self.shatter(outline, vel, WRECKAGE_LIFE, color);
+    }
+
+    // A spray of short streaks bouncing back from where a bullet hit.
+    pub fn sparks(&mut self, pos: Vec2, bullet_vel: Vec2, color: Color) {
+        let back = (-bullet_vel).to_angle();
+        for _ in 0..SPARKS_PER_IMPACT {
+            let angle = back + self.rng.gen_range(-1.0, 1.0);
+            let speed = SPARK_SPEED * self.rng.gen_range(0.5, 1.0);
+            self.spawn(Particle {
+                pos,
+                vel: Vec2::from_angle(angle) * speed,
+                rot: angle,
+                spin: 0.0,
+                half_len: 2.0,
+                life: SPARK_LIFE,
+                max_life: SPARK_LIFE,
+                color,
+            });
+        }
+    }
+}
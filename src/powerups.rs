@@ -0,0 +1,74 @@
+// Pickups dropped by asteroids the player shoots. Each arms the ship for a
+// few seconds and they stack, so a spread of rapid homing missiles is
+// possible. The weapons themselves are projectiles in `world`.
+use macroquad::math::Vec2;
+
+use crate::rng::Rng;
+
+// Chance a destroyed asteroid leaves a pickup behind.
+pub const DROP_CHANCE: f32 = 0.08;
+// Seconds a pickup waits to be collected.
+pub const PICKUP_LIFETIME: f64 = 10.0;
+pub const PICKUP_RADIUS: f32 = 10.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PowerUpKind {
+    Spread,
+    Rapid,
+    Laser,
+    Homing,
+    Shield,
+}
+
+pub const POWER_UPS: [PowerUpKind; 5] = [
+    PowerUpKind::Spread,
+    PowerUpKind::Rapid,
+    PowerUpKind::Laser,
+    PowerUpKind::Homing,
+    PowerUpKind::Shield,
+];
+
+impl PowerUpKind {
+    pub fn random(rng: &mut Rng) -> PowerUpKind {
+        POWER_UPS[rng.gen_range(0, POWER_UPS.len())]
+    }
+
+    // Seconds it lasts once collected.
+    pub fn duration(self) -> f32 {
+        match self {
+            PowerUpKind::Spread => 10.0,
+            PowerUpKind::Rapid => 10.0,
+            PowerUpKind::Laser => 8.0,
+            PowerUpKind::Homing => 10.0,
+            PowerUpKind::Shield => 8.0,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PowerUpKind::Spread => "Spread",
+            PowerUpKind::Rapid => "Rapid",
+            PowerUpKind::Laser => "Laser",
+            PowerUpKind::Homing => "Homing",
+            PowerUpKind::Shield => "Shield",
+        }
+    }
+
+    // Drawn inside the pickup.
+    pub fn letter(self) -> &'static str {
+        match self {
+            PowerUpKind::Spread => "S",
+            PowerUpKind::Rapid => "R",
+            PowerUpKind::Laser => "L",
+            PowerUpKind::Homing => "H",
+            PowerUpKind::Shield => "O",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PowerUp {
+    pub pos: Vec2,
+    pub kind: PowerUpKind,
+    pub expires_at: f64,
+}
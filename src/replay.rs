@@ -0,0 +1,274 @@
+// Recording of a run: the seed, the playfield size and the input of every
+// simulation step. Feeding it back through `World` reproduces the run exactly.
+use crate::palette::ColorPalette;
+use crate::world::{InputFrame, TICK, World};
+
+// Bumped whenever a change to the rules or the input format would make
+// existing recordings play out differently; older ones are refused.
+const FORMAT: &str = "asteroids-replay";
+const VERSION: u32 = 2;
+
+// Longest recording kept or loaded, a day of play, so a corrupt count in a
+// file cannot exhaust memory.
+pub const MAX_FRAMES: usize = 24 * 60 * 60 * 60;
+
+// Storage key of the most recently finished run.
+pub const LAST_REPLAY_KEY: &str = "last_replay.txt";
+
+pub struct Replay {
+    pub seed: u64,
+    pub width: f32,
+    pub height: f32,
+    pub asteroid_collisions: bool,
+    pub extra_life_every: u64,
+    pub final_score: u64,
+    pub inputs: Vec<InputFrame>,
+    // Set once a run outlasts `MAX_FRAMES`; such a recording could not be
+    // played back to the end, so it is not kept.
+    pub too_long: bool,
+}
+
+impl Replay {
+    pub fn new(world: &World) -> Replay {
+        let size = world.size();
+        Replay {
+            seed: world.seed,
+            width: size.x,
+            height: size.y,
+            asteroid_collisions: world.asteroid_collisions,
+            extra_life_every: world.extra_life_every(),
+            final_score: 0,
+            inputs: Vec::new(),
+            too_long: false,
+        }
+    }
+
+    // A fresh world in the state the recorded run started from.
+    pub fn start(&self, palettes: &[ColorPalette]) -> World {
+        let mut world = World::new(self.seed, palettes, self.width, self.height);
+        world.asteroid_collisions = self.asteroid_collisions;
+        world.set_extra_life_every(self.extra_life_every);
+        world
+    }
+
+    // Steps `world` with the input recorded for `tick`, moving on to the next
+    // level the moment the field is cleared, like the player did. Returns
+    // false once the recording is exhausted or the game is over.
+    pub fn play_tick(&self, world: &mut World, tick: usize, palettes: &[ColorPalette]) -> bool {
+        let Some(input) = self.inputs.get(tick) else {
+            return false;
+        };
+        world.step(input, TICK);
+        if world.is_cleared() {
+            world.next_level(palettes);
+        }
+        !world.game_over
+    }
+
+    // Replays the whole run without rendering and returns the score reached.
+    pub fn simulate(&self, palettes: &[ColorPalette]) -> u64 {
+        let mut world = self.start(palettes);
+        let mut tick = 0;
+        while self.play_tick(&mut world, tick, palettes) {
+            tick += 1;
+        }
+        world.player_score
+    }
+
+    // Inputs are stored run-length encoded with exact float bits, one
+    // `count rotate thrust buttons` line per run of identical frames.
+    pub fn to_text(&self) -> String {
+        let mut out = format!(
+            "{FORMAT} {VERSION}\nseed {}\nsize {} {}\nscore {}\ncollisions {}\nextra_life {}\n",
+            self.seed,
+            self.width.to_bits(),
+            self.height.to_bits(),
+            self.final_score,
+            self.asteroid_collisions as u8,
+            self.extra_life_every
+        );
+        let mut frames = self.inputs.iter().peekable();
+        while let Some(frame) = frames.next() {
+            let mut count = 1;
+            while frames.next_if(|f| *f == frame).is_some() {
+                count += 1;
+            }
+            out.push_str(&format!(
+                "{count} {:x} {:x} {}\n",
+                frame.rotate.to_bits(),
+                frame.thrust.to_bits(),
+                buttons(frame)
+            ));
+        }
+        out
+    }
+
+    pub fn parse(text: &str) -> Result<Replay, String> {
+        let mut lines = text.lines().enumerate();
+        let mut next_line = |what: &str| {
+            lines
+                .next()
+                .map(|(_, l)| l)
+                .ok_or_else(|| format!("missing {what}"))
+        };
+        let version = next_line("header")?
+            .strip_prefix(FORMAT)
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .ok_or("not a replay file")?;
+        if version != VERSION {
+            return Err(format!(
+                "replay format {version} is from another version of the game, this one plays format {VERSION}"
+            ));
+        }
+        let seed = field(next_line("seed")?, "seed")?;
+        let size = next_line("size")?;
+        let (w, h) = size
+            .strip_prefix("size ")
+            .and_then(|s| s.split_once(' '))
+            .ok_or("malformed size")?;
+        let width = f32::from_bits(w.parse().map_err(|_| "malformed size")?);
+        let height = f32::from_bits(h.parse().map_err(|_| "malformed size")?);
+        if !(width.is_finite() && height.is_finite() && width > 0.0 && height > 0.0) {
+            return Err("playfield size must be positive".to_string());
+        }
+        let final_score = field(next_line("score")?, "score")?;
+        let asteroid_collisions = field(next_line("collisions")?, "collisions")? != 0;
+        let extra_life_every = field(next_line("extra_life")?, "extra_life")?;
+
+        let mut inputs = Vec::new();
+        for (n, line) in lines {
+            let bad = || format!("malformed input on line {}", n + 1);
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let [count, rotate, thrust, buttons] = parts.as_slice() else {
+                return Err(bad());
+            };
+            let count: usize = count.parse().map_err(|_| bad())?;
+            if count > MAX_FRAMES - inputs.len() {
+                return Err(format!("replay is longer than {MAX_FRAMES} frames"));
+            }
+            let buttons: u8 = buttons.parse().map_err(|_| bad())?;
+            let frame = InputFrame {
+                rotate: f32::from_bits(u32::from_str_radix(rotate, 16).map_err(|_| bad())?),
+                thrust: f32::from_bits(u32::from_str_radix(thrust, 16).map_err(|_| bad())?),
+                fire: buttons & FIRE != 0,
+                hyperspace: buttons & HYPERSPACE != 0,
+                shield: buttons & SHIELD != 0,
+            };
+            inputs.extend(std::iter::repeat_n(frame, count));
+        }
+
+        Ok(Replay {
+            seed,
+            width,
+            height,
+            asteroid_collisions,
+            extra_life_every,
+            final_score,
+            inputs,
+            too_long: false,
+        })
+    }
+}
+
+// Bits of the `buttons` column.
+const FIRE: u8 = 1;
+const HYPERSPACE: u8 = 2;
+const SHIELD: u8 = 4;
+
+fn buttons(frame: &InputFrame) -> u8 {
+    let mut bits = 0;
+    if frame.fire {
+        bits |= FIRE;
+    }
+    if frame.hyperspace {
+        bits |= HYPERSPACE;
+    }
+    if frame.shield {
+        bits |= SHIELD;
+    }
+    bits
+}
+
+fn field(line: &str, name: &str) -> Result<u64, String> {
+    line.strip_prefix(name)
+        .and_then(|v| v.trim().parse().ok())
+        .ok_or_else(|| format!("malformed {name}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{FIELD_HEIGHT, FIELD_WIDTH};
+    use macroquad::color::{BLACK, WHITE};
+
+    fn palettes() -> Vec<ColorPalette> {
+        vec![ColorPalette {
+            name: "Test".to_string(),
+            background: BLACK,
+            ship: WHITE,
+            asteroid_colors: vec![WHITE],
+            next: None,
+        }]
+    }
+
+    // Header of a valid, empty recording on a 1x1 field (1.0 is 1065353216
+    // as f32 bits).
+    fn header() -> String {
+        format!(
+            "{FORMAT} {VERSION}\nseed 1\nsize 1065353216 1065353216\nscore 0\ncollisions 0\nextra_life 0\n"
+        )
+    }
+
+    #[test]
+    fn recorded_run_plays_back_to_the_same_score() {
+        let palettes = palettes();
+        let mut world = World::new(11, &palettes, FIELD_WIDTH, FIELD_HEIGHT);
+        world.asteroid_collisions = true;
+        let mut recording = Replay::new(&world);
+        for tick in 0..3000 {
+            let input = InputFrame {
+                rotate: if tick % 240 < 120 { 0.7 } else { -1.0 },
+                thrust: if tick % 100 < 20 { 1.0 } else { 0.0 },
+                fire: tick % 5 == 0,
+                hyperspace: false,
+                shield: tick % 400 > 370,
+            };
+            world.step(&input, TICK);
+            recording.inputs.push(input);
+            if world.game_over {
+                break;
+            }
+            if world.is_cleared() {
+                world.next_level(&palettes);
+            }
+        }
+        recording.final_score = world.player_score;
+        assert!(recording.final_score > 0);
+
+        let parsed = Replay::parse(&recording.to_text()).unwrap();
+        assert_eq!(parsed.inputs.len(), recording.inputs.len());
+        assert!(parsed.asteroid_collisions);
+        assert_eq!(parsed.simulate(&palettes), recording.final_score);
+    }
+
+    #[test]
+    fn refuses_other_versions() {
+        let text = header().replacen(&VERSION.to_string(), "1", 1);
+        let err = Replay::parse(&text).err().unwrap();
+        assert!(err.contains("another version"), "{err}");
+    }
+
+    #[test]
+    fn refuses_a_non_positive_size() {
+        let text = header().replace("size 1065353216 1065353216", "size 0 1065353216");
+        let err = Replay::parse(&text).err().unwrap();
+        assert_eq!(err, "playfield size must be positive");
+    }
+
+    #[test]
+    fn refuses_more_than_max_frames() {
+        let text = format!("{}{} 0 0 0\n", header(), MAX_FRAMES + 1);
+        let err = Replay::parse(&text).err().unwrap();
+        assert!(err.contains("longer than"), "{err}");
+    }
+}
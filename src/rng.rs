@@ -0,0 +1,78 @@
+// Small PCG32 generator owned by the game so that a run can be reproduced
+// from its seed. Same algorithm as macroquad's global `rand`, but the whole
+// state is a plain `Copy` value instead of a hidden global, and it can be
+// saved as JSON to carry on a run exactly where it was.
+use nanoserde::{DeJson, SerJson};
+
+const MULTIPLIER: u64 = 6364136223846793005;
+const INCREMENT: u64 = 1442695040888963407;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, SerJson, DeJson)]
+pub struct Rng {
+    state: u64,
+}
+
+pub trait SampleRange: Sized {
+    fn sample(rng: &mut Rng, low: Self, high: Self) -> Self;
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        let mut rng = Rng { state: 0 };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(INCREMENT);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    // Uniform value in `[low, high)`.
+    pub fn gen_range<T: SampleRange>(&mut self, low: T, high: T) -> T {
+        T::sample(self, low, high)
+    }
+
+    fn unit(&mut self) -> f64 {
+        self.next_u32() as f64 / (u32::MAX as f64 + 1.0)
+    }
+}
+
+macro_rules! impl_sample_range {
+    ($($ty:ty),*) => {
+        $(
+            impl SampleRange for $ty {
+                fn sample(rng: &mut Rng, low: Self, high: Self) -> Self {
+                    (low as f64 + (high as f64 - low as f64) * rng.unit()) as Self
+                }
+            }
+        )*
+    };
+}
+
+impl_sample_range!(f32, f64, u8, i32, usize);
+
+// Seed for a run the player did not ask to reproduce.
+pub fn fresh_seed() -> u64 {
+    (macroquad::miniquad::date::now() * 1000.0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_state_carries_on_the_same_sequence() {
+        let mut rng = Rng::new(42);
+        rng.next_u32();
+        let mut restored = Rng::deserialize_json(&rng.serialize_json()).unwrap();
+        for _ in 0..10 {
+            assert_eq!(rng.next_u32(), restored.next_u32());
+        }
+    }
+}
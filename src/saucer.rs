@@ -0,0 +1,130 @@
+// Flying saucers: cross the playfield on a wavy path and shoot back. Large
+// ones fire in random directions, small ones aim at the ship.
+use macroquad::math::{Vec2, vec2};
+
+use crate::rng::Rng;
+
+pub const LARGE_SAUCER_POINTS: u64 = 200;
+pub const SMALL_SAUCER_POINTS: u64 = 1000;
+// Seconds between saucers before the level's speed factor divides it; that
+// factor is already 1.15 on level 1, so the first level waits about 17s.
+pub const SAUCER_INTERVAL: f64 = 20.0;
+
+const LARGE_SIZE: f32 = 20.0;
+const SMALL_SIZE: f32 = 11.0;
+const LARGE_SPEED: f32 = 90.0;
+const SMALL_SPEED: f32 = 130.0;
+const LARGE_FIRE_RATE: f64 = 1.5;
+const SMALL_FIRE_RATE: f64 = 1.0;
+const WAVE_AMPLITUDE: f32 = 40.0;
+const WAVE_SPEED: f32 = 1.5;
+
+#[derive(Clone)]
+pub struct Saucer {
+    pub pos: Vec2,
+    pub prev_pos: Vec2,
+    pub small: bool,
+    pub destroyed: bool,
+    vel_x: f32,
+    base_y: f32,
+    phase: f32,
+    last_shot: f64,
+}
+
+impl Saucer {
+    // Enters from the left or right edge of a `size` playfield at time `now`.
+    pub fn spawn(rng: &mut Rng, size: Vec2, small: bool, speed_factor: f32, now: f64) -> Saucer {
+        let from_left = rng.gen_range(0, 2) == 0;
+        let speed = if small { SMALL_SPEED } else { LARGE_SPEED } * speed_factor;
+        let base_y = rng.gen_range(size.y * 0.15, size.y * 0.85);
+        let pos = vec2(if from_left { 0.0 } else { size.x }, base_y);
+        Saucer {
+            pos,
+            prev_pos: pos,
+            small,
+            destroyed: false,
+            vel_x: if from_left { speed } else { -speed },
+            base_y,
+            phase: 0.0,
+            last_shot: now,
+        }
+    }
+
+    pub fn radius(&self) -> f32 {
+        if self.small { SMALL_SIZE } else { LARGE_SIZE }
+    }
+
+    pub fn hull(&self) -> [Vec2; 6] {
+        saucer_outline(self.pos, self.radius()).0
+    }
+
+    pub fn points(&self) -> u64 {
+        if self.small {
+            SMALL_SAUCER_POINTS
+        } else {
+            LARGE_SAUCER_POINTS
+        }
+    }
+
+    pub fn update(&mut self, dt: f32, height: f32) {
+        self.prev_pos = self.pos;
+        self.phase += WAVE_SPEED * dt;
+        self.pos.x += self.vel_x * dt;
+        self.pos.y = (self.base_y + self.phase.sin() * WAVE_AMPLITUDE).rem_euclid(height);
+    }
+
+    // True once the saucer has crossed to the far edge.
+    pub fn has_crossed(&self, width: f32) -> bool {
+        self.pos.x < 0.0 || self.pos.x > width
+    }
+
+    // Direction of the next shot if the saucer is ready to fire. Small saucers
+    // aim at `target` with an error that shrinks as `accuracy` grows.
+    pub fn fire(
+        &mut self,
+        rng: &mut Rng,
+        now: f64,
+        target: Option<Vec2>,
+        accuracy: f32,
+    ) -> Option<Vec2> {
+        let rate = if self.small {
+            SMALL_FIRE_RATE
+        } else {
+            LARGE_FIRE_RATE
+        };
+        if now - self.last_shot < rate {
+            return None;
+        }
+        self.last_shot = now;
+        let angle = match target {
+            Some(t) if self.small => {
+                let to = t - self.pos;
+                let spread = 0.5 / accuracy.max(1.0);
+                to.y.atan2(to.x) + rng.gen_range(-spread, spread)
+            }
+            _ => rng.gen_range(0.0, std::f32::consts::TAU),
+        };
+        Some(vec2(angle.cos(), angle.sin()))
+    }
+}
+
+// Outline of a saucer of `radius` centred on `pos`: the hull hexagon followed
+// by the four points of the dome.
+pub fn saucer_outline(pos: Vec2, radius: f32) -> ([Vec2; 6], [Vec2; 4]) {
+    let s = radius;
+    let hull = [
+        vec2(-s, 0.0),
+        vec2(-s * 0.5, -s * 0.35),
+        vec2(s * 0.5, -s * 0.35),
+        vec2(s, 0.0),
+        vec2(s * 0.5, s * 0.35),
+        vec2(-s * 0.5, s * 0.35),
+    ];
+    let dome = [
+        vec2(-s * 0.4, -s * 0.35),
+        vec2(-s * 0.25, -s * 0.7),
+        vec2(s * 0.25, -s * 0.7),
+        vec2(s * 0.4, -s * 0.35),
+    ];
+    (hull.map(|p| pos + p), dome.map(|p| pos + p))
+}
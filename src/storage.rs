@@ -0,0 +1,50 @@
+// Small key/value text store. Native builds keep one file per key in the
+// working directory, the wasm build uses the browser's localStorage through
+// the `first_game_storage` plugin registered in index.html.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load(key: &str) -> Option<String> {
+    std::fs::read_to_string(key).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save(key: &str, contents: &str) -> Result<(), String> {
+    std::fs::write(key, contents).map_err(|e| format!("could not write {key}: {e}"))
+}
+
+#[cfg(target_arch = "wasm32")]
+unsafe extern "C" {
+    fn storage_len(key: *const u8, key_len: u32) -> i32;
+    fn storage_get(key: *const u8, key_len: u32, buf: *mut u8);
+    fn storage_set(key: *const u8, key_len: u32, val: *const u8, val_len: u32);
+}
+
+#[cfg(target_arch = "wasm32")]
+#[unsafe(no_mangle)]
+pub extern "C" fn first_game_storage_crate_version() -> u32 {
+    1
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn load(key: &str) -> Option<String> {
+    let len = unsafe { storage_len(key.as_ptr(), key.len() as u32) };
+    if len < 0 {
+        return None;
+    }
+    let mut buf = vec![0u8; len as usize];
+    unsafe { storage_get(key.as_ptr(), key.len() as u32, buf.as_mut_ptr()) };
+    String::from_utf8(buf).ok()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save(key: &str, contents: &str) -> Result<(), String> {
+    unsafe {
+        storage_set(
+            key.as_ptr(),
+            key.len() as u32,
+            contents.as_ptr(),
+            contents.len() as u32,
+        )
+    };
+    Ok(())
+}
@@ -0,0 +1,475 @@
+// On-screen controls. Two layouts: the original row of rotate/thrust buttons,
+// and a floating virtual stick that points the ship where it is pushed and
+// thrusts with the deflection. Either can auto-fire or fire on tap.
+use macroquad::prelude::*;
+
+use crate::world::{InputFrame, SHIP_ROT_SPEED, TICK};
+
+const TOUCH_KEY: &str = "touch.txt";
+// Stick deflection below this fraction of its radius is ignored.
+const STICK_DEADZONE: f32 = 0.2;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TouchScheme {
+    Buttons,
+    Joystick,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TouchFire {
+    Auto,
+    Tap,
+}
+
+#[derive(Clone, Copy)]
+pub struct TouchSettings {
+    pub scheme: TouchScheme,
+    pub fire: TouchFire,
+}
+
+impl Default for TouchSettings {
+    fn default() -> TouchSettings {
+        TouchSettings {
+            scheme: TouchScheme::Buttons,
+            fire: TouchFire::Auto,
+        }
+    }
+}
+
+impl TouchSettings {
+    pub fn load() -> TouchSettings {
+        let Some(text) = crate::storage::load(TOUCH_KEY) else {
+            return TouchSettings::default();
+        };
+        let mut settings = TouchSettings::default();
+        for word in text.split_whitespace() {
+            match word {
+                "buttons" => settings.scheme = TouchScheme::Buttons,
+                "joystick" => settings.scheme = TouchScheme::Joystick,
+                "autofire" => settings.fire = TouchFire::Auto,
+                "tapfire" => settings.fire = TouchFire::Tap,
+                _ => {}
+            }
+        }
+        settings
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let scheme = match self.scheme {
+            TouchScheme::Buttons => "buttons",
+            TouchScheme::Joystick => "joystick",
+        };
+        let fire = match self.fire {
+            TouchFire::Auto => "autofire",
+            TouchFire::Tap => "tapfire",
+        };
+        crate::storage::save(TOUCH_KEY, &format!("{scheme} {fire}\n"))
+    }
+
+    // One line of help text for the info screen.
+    pub fn help(&self) -> &'static str {
+        match (self.scheme, self.fire) {
+            (TouchScheme::Buttons, TouchFire::Auto) => {
+                "Move with [buttons] ship will autofire, [WARP] to jump, hold [SHIELD] to block."
+            }
+            (TouchScheme::Buttons, TouchFire::Tap) => {
+                "Move with [buttons], tap anywhere to fire, [WARP] to jump, hold [SHIELD] to block."
+            }
+            (TouchScheme::Joystick, TouchFire::Auto) => {
+                "Drag on the left to steer and thrust, ship will autofire, [WARP] to jump, hold [SHIELD] to block."
+            }
+            (TouchScheme::Joystick, TouchFire::Tap) => {
+                "Drag on the left to steer and thrust, [FIRE] to shoot, [WARP] to jump, hold [SHIELD] to block."
+            }
+        }
+    }
+}
+
+struct TouchButtons {
+    left: Rect,
+    right: Rect,
+    thrust: Rect,
+    hyperspace: Rect,
+    shield: Rect,
+}
+
+fn touch_buttons() -> TouchButtons {
+    let scr_w = screen_width();
+    let scr_h = screen_height();
+    let btn_size = scr_w * 0.2;
+    let rotation_btn_w = scr_w / 4.;
+
+    TouchButtons {
+        left: Rect::new(0.0, scr_h - btn_size, rotation_btn_w, btn_size),
+        right: Rect::new(rotation_btn_w, scr_h - btn_size, rotation_btn_w, btn_size),
+        thrust: Rect::new(scr_w / 2., scr_h - btn_size, scr_w / 2., btn_size),
+        hyperspace: Rect::new(
+            scr_w - btn_size,
+            scr_h - btn_size * 1.6,
+            btn_size,
+            btn_size * 0.5,
+        ),
+        shield: Rect::new(
+            scr_w - btn_size * 2.2,
+            scr_h - btn_size * 1.6,
+            btn_size,
+            btn_size * 0.5,
+        ),
+    }
+}
+
+// Top left, clear of the score and of both layouts.
+pub fn pause_button() -> Rect {
+    let size = screen_width().min(screen_height()) * 0.1;
+    Rect::new(size * 0.3, size * 0.3, size, size)
+}
+
+// Stick reach, and the fire, warp and shield buttons on the right.
+fn stick_layout() -> (f32, Rect, Rect, Rect) {
+    let radius = screen_width().min(screen_height()) * 0.12;
+    let size = radius * 2.2;
+    let fire = Rect::new(
+        screen_width() - size - radius * 0.4,
+        screen_height() - size - radius * 0.4,
+        size,
+        size,
+    );
+    let hyperspace = Rect::new(fire.x, fire.y - radius * 1.2, size, radius * 0.9);
+    let shield = Rect::new(fire.x, hyperspace.y - radius * 1.2, size, radius * 0.9);
+    (radius, fire, hyperspace, shield)
+}
+
+pub struct TouchControls {
+    pub settings: TouchSettings,
+    // Touch id, where it went down and where it is now, while the stick is
+    // held.
+    stick: Option<(u64, Vec2, Vec2)>,
+}
+
+impl TouchControls {
+    pub fn new(settings: TouchSettings) -> TouchControls {
+        TouchControls {
+            settings,
+            stick: None,
+        }
+    }
+
+    // `ship_rot` lets the stick turn the ship toward where it points.
+    // `frame_time` is the time the input will be played for, which may be
+    // several simulation steps.
+    pub fn input(&mut self, ship_rot: f32, frame_time: f32) -> InputFrame {
+        match self.settings.scheme {
+            TouchScheme::Buttons => self.buttons_input(),
+            TouchScheme::Joystick => self.stick_input(ship_rot, frame_time),
+        }
+    }
+
+    fn buttons_input(&self) -> InputFrame {
+        let buttons = touch_buttons();
+        let mut input = InputFrame {
+            fire: self.settings.fire == TouchFire::Auto,
+            ..Default::default()
+        };
+
+        for touch in touches().iter() {
+            let p = touch.position;
+            if buttons.left.contains(p) {
+                input.rotate -= 0.6;
+            } else if buttons.right.contains(p) {
+                input.rotate += 0.6;
+            } else if buttons.thrust.contains(p) {
+                input.thrust = 0.125;
+            } else if buttons.hyperspace.contains(p) {
+                input.hyperspace = true;
+            } else if buttons.shield.contains(p) {
+                input.shield = true;
+            } else if self.settings.fire == TouchFire::Tap && !pause_button().contains(p) {
+                input.fire = true;
+            }
+        }
+        input
+    }
+
+    fn stick_input(&mut self, ship_rot: f32, frame_time: f32) -> InputFrame {
+        let (radius, fire, hyperspace, shield) = stick_layout();
+        let mut input = InputFrame {
+            fire: self.settings.fire == TouchFire::Auto,
+            ..Default::default()
+        };
+
+        let all = touches();
+        for touch in all.iter() {
+            let p = touch.position;
+            match self.stick {
+                Some((id, origin, _)) if id == touch.id => {
+                    self.stick = match touch.phase {
+                        TouchPhase::Ended | TouchPhase::Cancelled => None,
+                        _ => Some((id, origin, p)),
+                    };
+                }
+                None if touch.phase == TouchPhase::Started
+                    && p.x < screen_width() / 2.0
+                    && !pause_button().contains(p) =>
+                {
+                    self.stick = Some((touch.id, p, p));
+                }
+                _ => {
+                    if hyperspace.contains(p) {
+                        input.hyperspace = true;
+                    } else if shield.contains(p) {
+                        input.shield = true;
+                    } else if fire.contains(p) {
+                        input.fire = true;
+                    }
+                }
+            }
+        }
+        // The stick's finger can vanish without an end event, e.g. when the
+        // game loses focus.
+        if let Some((id, _, _)) = self.stick
+            && !all.iter().any(|t| t.id == id)
+        {
+            self.stick = None;
+        }
+
+        if let Some((_, origin, pos)) = self.stick {
+            let d = pos - origin;
+            let deflection = d.length() / radius;
+            if deflection > STICK_DEADZONE {
+                // Turn at full speed until the remaining angle fits in the
+                // frame, or in one step when a frame is shorter than that.
+                let target = d.x.atan2(-d.y).to_degrees();
+                let diff = (target - ship_rot + 540.0).rem_euclid(360.0) - 180.0;
+                let turn_time = frame_time.max(TICK);
+                input.rotate = (diff / (SHIP_ROT_SPEED * turn_time)).clamp(-1.0, 1.0);
+                input.thrust = ((deflection - STICK_DEADZONE) / (1.0 - STICK_DEADZONE)).min(1.0);
+            }
+        }
+        input
+    }
+
+    pub fn draw(&self) {
+        match self.settings.scheme {
+            TouchScheme::Buttons => draw_buttons(self.settings.fire),
+            TouchScheme::Joystick => self.draw_stick(),
+        }
+
+        let pause = pause_button();
+        draw_rectangle(
+            pause.x,
+            pause.y,
+            pause.w,
+            pause.h,
+            Color::new(0.0, 0.0, 0.0, 0.1),
+        );
+        draw_rectangle_lines(pause.x, pause.y, pause.w, pause.h, 1.0, WHITE);
+        // Two bars, the usual pause symbol.
+        let bar_w = pause.w * 0.15;
+        let bar_h = pause.h * 0.5;
+        let top = pause.y + (pause.h - bar_h) / 2.0;
+        draw_rectangle(pause.x + pause.w * 0.3, top, bar_w, bar_h, WHITE);
+        draw_rectangle(pause.x + pause.w * 0.55, top, bar_w, bar_h, WHITE);
+    }
+
+    fn draw_stick(&self) {
+        let (radius, fire, hyperspace, shield) = stick_layout();
+        let fs = screen_width().max(screen_height()) * 0.025;
+        let fill = Color::new(0.0, 0.0, 0.0, 0.1);
+
+        match self.stick {
+            Some((_, origin, pos)) => {
+                let knob = origin + (pos - origin).clamp_length_max(radius);
+                draw_circle_lines(origin.x, origin.y, radius, 1.0, WHITE);
+                draw_circle(knob.x, knob.y, radius * 0.35, fill);
+                draw_circle_lines(knob.x, knob.y, radius * 0.35, 1.0, WHITE);
+            }
+            None => {
+                // Where to put a thumb; the stick appears wherever it lands.
+                let hint = vec2(radius * 1.5, screen_height() - radius * 1.5);
+                draw_circle(hint.x, hint.y, radius, fill);
+                draw_circle_lines(hint.x, hint.y, radius, 1.0, WHITE);
+            }
+        }
+
+        if self.settings.fire == TouchFire::Tap {
+            let c = fire.center();
+            draw_circle(c.x, c.y, fire.w / 2.0, fill);
+            draw_circle_lines(c.x, c.y, fire.w / 2.0, 1.0, WHITE);
+            let ts = measure_text("FIRE", None, fs as u16, 1.0);
+            draw_text(
+                "FIRE",
+                c.x - ts.width / 2.0,
+                c.y + ts.height / 2.0,
+                fs,
+                WHITE,
+            );
+        }
+
+        draw_rectangle(hyperspace.x, hyperspace.y, hyperspace.w, hyperspace.h, fill);
+        draw_rectangle_lines(
+            hyperspace.x,
+            hyperspace.y,
+            hyperspace.w,
+            hyperspace.h,
+            1.0,
+            WHITE,
+        );
+        let ts = measure_text("WARP", None, fs as u16, 1.0);
+        draw_text(
+            "WARP",
+            hyperspace.x + hyperspace.w / 2.0 - ts.width / 2.0,
+            hyperspace.y + hyperspace.h / 2.0 + ts.height / 2.0,
+            fs,
+            WHITE,
+        );
+
+        draw_rectangle(shield.x, shield.y, shield.w, shield.h, fill);
+        draw_rectangle_lines(shield.x, shield.y, shield.w, shield.h, 1.0, WHITE);
+        let ts = measure_text("SHIELD", None, fs as u16, 1.0);
+        draw_text(
+            "SHIELD",
+            shield.x + shield.w / 2.0 - ts.width / 2.0,
+            shield.y + shield.h / 2.0 + ts.height / 2.0,
+            fs,
+            WHITE,
+        );
+    }
+}
+
+// The original layout: rotate left and right, thrust and warp.
+fn draw_buttons(fire: TouchFire) {
+    let scr_w = screen_width();
+    let scr_h = screen_height();
+    let btn_size = scr_w * 0.2;
+
+    let fs = screen_width().max(screen_height()) * 0.025;
+
+    let TouchButtons {
+        left: left_btn,
+        right: right_btn,
+        thrust: thrust_btn,
+        hyperspace: hyper_btn,
+        shield: shield_btn,
+    } = touch_buttons();
+    let alpha = 0.1;
+    draw_rectangle(
+        left_btn.x,
+        left_btn.y,
+        left_btn.w,
+        left_btn.h,
+        Color::new(0.0, 0.0, 0.0, alpha),
+    );
+    draw_rectangle(
+        right_btn.x,
+        right_btn.y,
+        right_btn.w,
+        right_btn.h,
+        Color::new(0.0, 0.0, 0.0, alpha),
+    );
+    draw_rectangle(
+        thrust_btn.x,
+        thrust_btn.y,
+        thrust_btn.w,
+        thrust_btn.h,
+        Color::new(0.0, 0.0, 0.0, alpha),
+    );
+    draw_rectangle(
+        hyper_btn.x,
+        hyper_btn.y,
+        hyper_btn.w,
+        hyper_btn.h,
+        Color::new(0.0, 0.0, 0.0, alpha),
+    );
+    draw_rectangle(
+        shield_btn.x,
+        shield_btn.y,
+        shield_btn.w,
+        shield_btn.h,
+        Color::new(0.0, 0.0, 0.0, alpha),
+    );
+
+    draw_rectangle_lines(left_btn.x, left_btn.y, left_btn.w, left_btn.h, 1.0, WHITE);
+    draw_rectangle_lines(
+        right_btn.x,
+        right_btn.y,
+        right_btn.w,
+        right_btn.h,
+        1.0,
+        WHITE,
+    );
+    draw_rectangle_lines(
+        thrust_btn.x,
+        thrust_btn.y,
+        thrust_btn.w,
+        thrust_btn.h,
+        1.0,
+        WHITE,
+    );
+    draw_rectangle_lines(
+        hyper_btn.x,
+        hyper_btn.y,
+        hyper_btn.w,
+        hyper_btn.h,
+        1.0,
+        WHITE,
+    );
+    draw_rectangle_lines(
+        shield_btn.x,
+        shield_btn.y,
+        shield_btn.w,
+        shield_btn.h,
+        1.0,
+        WHITE,
+    );
+
+    let small = btn_size * 0.3;
+    draw_text(
+        "<",
+        left_btn.x + left_btn.w / 2.0 - small / 2.0,
+        left_btn.y + left_btn.h / 2.0 + small / 2.0,
+        small,
+        WHITE,
+    );
+    draw_text(
+        ">",
+        right_btn.x + right_btn.w / 2.0 - small / 2.0,
+        right_btn.y + right_btn.h / 2.0 + small / 2.0,
+        small,
+        WHITE,
+    );
+    draw_text(
+        "^",
+        thrust_btn.x + thrust_btn.w / 2.0 - small / 2.0,
+        thrust_btn.y + thrust_btn.h / 2.0 + small / 2.0,
+        small,
+        WHITE,
+    );
+    let hyper_ts = measure_text("WARP", None, fs as u16, 1.0);
+    draw_text(
+        "WARP",
+        hyper_btn.x + hyper_btn.w / 2.0 - hyper_ts.width / 2.0,
+        hyper_btn.y + hyper_btn.h / 2.0 + hyper_ts.height / 2.0,
+        fs,
+        WHITE,
+    );
+    let shield_ts = measure_text("SHIELD", None, fs as u16, 1.0);
+    draw_text(
+        "SHIELD",
+        shield_btn.x + shield_btn.w / 2.0 - shield_ts.width / 2.0,
+        shield_btn.y + shield_btn.h / 2.0 + shield_ts.height / 2.0,
+        fs,
+        WHITE,
+    );
+
+    if fire == TouchFire::Tap {
+        let fire_label = "Tap anywhere to FIRE";
+        let ts = measure_text(fire_label, None, fs as u16, 1.0);
+        draw_text(
+            fire_label,
+            scr_w / 2.0 - ts.width / 2.0,
+            scr_h - btn_size - 12.0,
+            fs,
+            WHITE,
+        );
+    }
+}
@@ -0,0 +1,1214 @@
+// Headless game simulation. Nothing in here may touch the window, the clock or
+// the renderer so that it can be stepped without a display.
+use macroquad::color::Color;
+use macroquad::math::{Vec2, vec2};
+
+use crate::collision::{
+    polygons_overlap, segment_hits_polygon, segment_hits_star, star_overlaps, stars_overlap,
+};
+use crate::palette::{ColorPalette, pick_palette_for_level};
+use crate::powerups::{
+    DROP_CHANCE, PICKUP_LIFETIME, PICKUP_RADIUS, POWER_UPS, PowerUp, PowerUpKind,
+};
+use crate::rng::Rng;
+use crate::saucer::{SAUCER_INTERVAL, Saucer};
+
+pub const SHIP_HEIGHT: f32 = 25.;
+pub const SHIP_BASE: f32 = 22.;
+// Size of the playfield new games are played on, in world units. The window
+// only changes how big it looks.
+pub const FIELD_WIDTH: f32 = 1280.0;
+pub const FIELD_HEIGHT: f32 = 720.0;
+// Length of one simulation step in seconds.
+pub const TICK: f32 = 1.0 / 60.0;
+const FIRE_RATE: f64 = 0.25;
+// Rapid fire divides the time between shots by this.
+const RAPID_FIRE: f64 = 3.0;
+// Degrees between the three shots of a spread.
+const SPREAD_ANGLE: f32 = 15.0;
+const BULLET_LIFETIME: f64 = 1.5;
+const LASER_LIFETIME: f64 = 0.6;
+const LASER_DAMAGE: u8 = 2;
+// Asteroids or saucers a laser passes through after the first.
+const LASER_PIERCE: u32 = 3;
+const MISSILE_LIFETIME: f64 = 2.5;
+// Radians per second a homing missile can turn.
+const MISSILE_TURN: f32 = 4.0;
+const SCORE_MULTIPLIER: u8 = 5;
+const STARTING_LIVES: u32 = 3;
+pub const EXTRA_LIFE_EVERY: u64 = 1000;
+// Thresholds the player can pick from; 0 never awards one.
+pub const EXTRA_LIFE_CHOICES: [u64; 5] = [0, 500, 1000, 2000, 5000];
+// After losing a life the ship waits at least `RESPAWN_DELAY` seconds for the
+// centre to clear, and comes back regardless after `RESPAWN_TIMEOUT`.
+const RESPAWN_DELAY: f64 = 1.0;
+const RESPAWN_TIMEOUT: f64 = 5.0;
+const RESPAWN_CLEARANCE: f32 = SHIP_HEIGHT * 4.0;
+const INVULNERABLE_TIME: f32 = 3.0;
+const HYPERSPACE_COOLDOWN: f64 = 3.0;
+// Chance that a hyperspace jump destroys the ship on arrival.
+const HYPERSPACE_FAILURE: f32 = 0.1;
+// How long the ship is gone between warping out and warping in.
+pub const WARP_TIME: f32 = 0.5;
+
+// Shield energy runs from 0 to 1; these are per second.
+const SHIELD_DRAIN: f32 = 0.5;
+const SHIELD_RECHARGE: f32 = 0.125;
+pub const SHIELD_RADIUS: f32 = SHIP_HEIGHT;
+// Mass of the ship, and of an asteroid per unit of its outline's area, for
+// bounces off the shield and between asteroids.
+const SHIP_MASS: f32 = 1.0;
+const ASTEROID_DENSITY: f32 = 0.0008;
+// An asteroid's corners lie between this fraction of its size and its size
+// from the centre.
+const ASTEROID_JAGGEDNESS: f32 = 0.35;
+
+// All speeds below are per second.
+pub const SHIP_ROT_SPEED: f32 = 300.0;
+const SHIP_THRUST: f32 = 7200.0;
+const SHIP_DRAG: f32 = 0.6;
+const SHIP_MAX_SPEED: f32 = 300.0;
+const BULLET_SPEED: f32 = 420.0;
+const LASER_SPEED: f32 = 900.0;
+const MISSILE_SPEED: f32 = 300.0;
+const ASTEROID_SPEED: f32 = 60.0;
+const ASTEROID_ROT_SPEED: f32 = 120.0;
+// Fraction of the smaller screen side kept free of asteroids around the ship
+// when a wave starts.
+const SPAWN_CLEARANCE: f32 = 0.3;
+// No asteroid may head into the clearance zone within this many seconds of
+// moving.
+const SPAWN_HEADING_GRACE: f32 = 1.0;
+// Seconds a new wave fades in, frozen and harmless, before it starts moving.
+pub const SPAWN_IN_TIME: f32 = 0.75;
+
+#[derive(Clone)]
+pub struct Ship {
+    pub pos: Vec2,
+    pub rot: f32,
+    pub vel: Vec2,
+    pub prev_pos: Vec2,
+    pub prev_rot: f32,
+    // Seconds of invulnerability left after a respawn.
+    pub invulnerable: f32,
+    // Seconds left in a hyperspace jump, and where the jump started.
+    pub warp: f32,
+    pub warp_from: Vec2,
+    // Whether the engine fired on the last step, for the exhaust flame.
+    pub thrusting: bool,
+    // Shield charge from 0 to 1, and whether it was up on the last step.
+    pub energy: f32,
+    pub shielded: bool,
+    // Set when the charge runs out, by a hit or by holding it down; the
+    // shield stays down until it has recharged fully.
+    pub depleted: bool,
+    warp_fails: bool,
+}
+
+// How a projectile moves once fired.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Behaviour {
+    Straight,
+    // Straight and fast, drawn as a beam.
+    Beam,
+    // Steers toward the nearest asteroid or saucer.
+    Homing,
+}
+
+#[derive(Clone)]
+pub struct Projectile {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub prev_pos: Vec2,
+    // Saucer shots hit the ship and asteroids but score nothing.
+    pub from_saucer: bool,
+    pub behaviour: Behaviour,
+    // Sides knocked off an asteroid it hits; 1 splits it as usual.
+    pub damage: u8,
+    // How many more things it can pass through before it is spent.
+    pierce: u32,
+    // Numbers the ship's shots so fragments can ignore the one that made
+    // them; saucer shots are all 0.
+    id: u32,
+    lifetime: f64,
+    shot_at: f64,
+    collided: bool,
+}
+
+#[derive(Clone)]
+pub struct Asteroid {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub prev_pos: Vec2,
+    pub rot: f32,
+    pub prev_rot: f32,
+    pub rot_speed: f32,
+    pub size: f32,
+    // Scores and splits like a polygon with this many sides; the outline has
+    // more corners than that.
+    pub sides: u8,
+    // Corners around the centre before rotation, in units of `size`.
+    pub shape: Vec<Vec2>,
+    // The piercing shot that split it off, which passes through it rather
+    // than carving up the whole family.
+    immune_to: Option<u32>,
+    pub collided: bool,
+    pub color: Color,
+}
+
+// Player intent for a single simulation step, sampled by the frontend.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct InputFrame {
+    // -1.0 is full speed counter-clockwise, 1.0 full speed clockwise.
+    pub rotate: f32,
+    // 0.0 is no thrust, 1.0 full thrust.
+    pub thrust: f32,
+    pub fire: bool,
+    pub hyperspace: bool,
+    pub shield: bool,
+}
+
+// Something worth a sound or an effect that happened during the last `step`.
+#[derive(Clone)]
+pub enum WorldEvent {
+    Fire,
+    // `scale` is the asteroid's size relative to a fresh wave asteroid.
+    AsteroidBroken {
+        scale: f32,
+        outline: Vec<Vec2>,
+        vel: Vec2,
+        color: Color,
+    },
+    SaucerDestroyed {
+        pos: Vec2,
+        radius: f32,
+    },
+    ShipDestroyed {
+        outline: [Vec2; 3],
+        vel: Vec2,
+    },
+    // A projectile struck something at `pos`.
+    Impact {
+        pos: Vec2,
+        vel: Vec2,
+    },
+    // The ship collected a power-up.
+    PowerUp,
+    // The shield took a hit at `pos`, pushing the other object along `vel`.
+    ShieldHit {
+        pos: Vec2,
+        vel: Vec2,
+    },
+}
+
+#[derive(Clone)]
+pub struct World {
+    pub ship: Ship,
+    pub projectiles: Vec<Projectile>,
+    pub asteroids: Vec<Asteroid>,
+    pub saucers: Vec<Saucer>,
+    pub power_ups: Vec<PowerUp>,
+    // Seconds left on each power-up, indexed by `PowerUpKind`.
+    pub powers: [f32; POWER_UPS.len()],
+    pub player_score: u64,
+    pub level_multiplier: f64,
+    pub lives: u32,
+    // Score at which the next extra life is awarded; every
+    // `extra_life_every` points after that gives another one.
+    next_extra_life: u64,
+    extra_life_every: u64,
+    // Set when the ship is hit with no lives left.
+    pub game_over: bool,
+    // When the ship was last lost, while it waits to respawn.
+    pub ship_lost_at: Option<f64>,
+    // Index into the palette list the level is drawn with.
+    pub palette: usize,
+    pub seed: u64,
+    // When the current wave was spawned.
+    pub wave_started_at: f64,
+    // Whether asteroids bounce off each other instead of passing through.
+    pub asteroid_collisions: bool,
+    // Filled during each `step`, cleared at the start of the next.
+    pub events: Vec<WorldEvent>,
+    rng: Rng,
+    size: Vec2,
+    time: f64,
+    last_shot: f64,
+    last_hyperspace: f64,
+    next_saucer_at: f64,
+    asteroid_colors: Vec<Color>,
+    shots_fired: u32,
+}
+
+impl Ship {
+    fn new(pos: Vec2) -> Ship {
+        Ship {
+            pos,
+            rot: 0.0,
+            vel: Vec2::ZERO,
+            prev_pos: pos,
+            prev_rot: 0.0,
+            invulnerable: 0.0,
+            warp: 0.0,
+            warp_from: pos,
+            thrusting: false,
+            energy: 1.0,
+            shielded: false,
+            depleted: false,
+            warp_fails: false,
+        }
+    }
+
+    // Knocks `a`, which is `away` from the ship, off the shield as an
+    // elastic collision between the two, and spends the shield doing it.
+    fn bounce(&mut self, a: &mut Asteroid, away: Vec2, size: Vec2) {
+        let n = away.normalize_or(Vec2::X);
+        let mass = a.mass();
+        let closing = (a.vel - self.vel).dot(n);
+        if closing < 0.0 {
+            let impulse = -2.0 * closing / (1.0 / mass + 1.0 / SHIP_MASS);
+            a.vel += n * impulse / mass;
+            self.vel -= n * impulse / SHIP_MASS;
+        }
+        // Pushed clear so it is not caught again on the next step.
+        a.pos = wrap_around(&(self.pos + n * (SHIELD_RADIUS + a.size)), size);
+        self.spend_shield();
+    }
+
+    // The shield takes one hit, then has to recharge from empty.
+    fn spend_shield(&mut self) {
+        self.energy = 0.0;
+        self.shielded = false;
+        self.depleted = true;
+    }
+}
+
+impl Projectile {
+    // A plain shot, as fired by saucers and the unarmed ship.
+    fn bullet(pos: Vec2, vel: Vec2, from_saucer: bool, now: f64) -> Projectile {
+        Projectile {
+            pos,
+            vel,
+            prev_pos: pos,
+            from_saucer,
+            behaviour: Behaviour::Straight,
+            damage: 1,
+            pierce: 0,
+            id: 0,
+            lifetime: BULLET_LIFETIME,
+            shot_at: now,
+            collided: false,
+        }
+    }
+
+    // A player shot heading along `dir`, armed by whichever of the laser and
+    // homing power-ups are running; the laser wins if both are.
+    fn armed(pos: Vec2, dir: Vec2, powers: &[f32], now: f64) -> Projectile {
+        let on = |kind: PowerUpKind| powers[kind as usize] > 0.0;
+        if on(PowerUpKind::Laser) {
+            Projectile {
+                behaviour: Behaviour::Beam,
+                damage: LASER_DAMAGE,
+                pierce: LASER_PIERCE,
+                lifetime: LASER_LIFETIME,
+                ..Projectile::bullet(pos, dir * LASER_SPEED, false, now)
+            }
+        } else if on(PowerUpKind::Homing) {
+            Projectile {
+                behaviour: Behaviour::Homing,
+                lifetime: MISSILE_LIFETIME,
+                ..Projectile::bullet(pos, dir * MISSILE_SPEED, false, now)
+            }
+        } else {
+            Projectile::bullet(pos, dir * BULLET_SPEED, false, now)
+        }
+    }
+
+    // Uses up one pierce, or stops the projectile if it has none left.
+    fn hit(&mut self) {
+        if self.pierce > 0 {
+            self.pierce -= 1;
+        } else {
+            self.collided = true;
+        }
+    }
+}
+
+impl Asteroid {
+    // The outline it is drawn and collides as.
+    pub fn outline(&self) -> Vec<Vec2> {
+        self.outline_at(self.pos, self.rot, self.size)
+    }
+
+    // The outline centred on `pos`, turned `rot` degrees and `size` across.
+    pub fn outline_at(&self, pos: Vec2, rot: f32, size: f32) -> Vec<Vec2> {
+        let turn = Vec2::from_angle(rot.to_radians()) * size;
+        self.shape.iter().map(|p| pos + turn.rotate(*p)).collect()
+    }
+
+    // From the area of the outline, so big rocks are heaviest.
+    fn mass(&self) -> f32 {
+        let n = self.shape.len();
+        let area = (0..n)
+            .map(|i| self.shape[i].perp_dot(self.shape[(i + 1) % n]))
+            .sum::<f32>()
+            / 2.0;
+        ASTEROID_DENSITY * area * self.size * self.size
+    }
+}
+
+// A rock outline with corners at jittered angles and distances, two for
+// each side plus four so even the smallest look rough.
+fn jagged_shape(rng: &mut Rng, sides: u8) -> Vec<Vec2> {
+    let corners = sides as usize * 2 + 4;
+    let step = std::f32::consts::TAU / corners as f32;
+    (0..corners)
+        .map(|i| {
+            let angle = (i as f32 + rng.gen_range(-0.35, 0.35)) * step;
+            Vec2::from_angle(angle) * rng.gen_range(1.0 - ASTEROID_JAGGEDNESS, 1.0)
+        })
+        .collect()
+}
+
+// The outline of a fragment of `parent` flying off along `dir`. The half of
+// the parent's outline facing `dir` is opened out into most of the fragment,
+// and a fresh break closes it off behind.
+fn fragment_shape(rng: &mut Rng, parent: &Asteroid, dir: Vec2) -> Vec<Vec2> {
+    use std::f32::consts::{FRAC_PI_2, PI};
+    // Angles are measured around the parent's centre from `dir`, in the
+    // parent's unrotated frame.
+    let facing = dir.to_angle() - parent.rot.to_radians();
+    let from_facing = |p: Vec2| Vec2::from_angle(-facing).rotate(p).to_angle();
+    let mut corners: Vec<(f32, f32)> = parent
+        .shape
+        .iter()
+        .map(|p| (from_facing(*p), p.length()))
+        .filter(|(a, _)| a.abs() < FRAC_PI_2)
+        .map(|(a, r)| (a * 1.5, r))
+        .collect();
+    for i in 0..3 {
+        let a = PI * (0.8 + 0.2 * i as f32) + rng.gen_range(-0.1, 0.1);
+        let a = if a > PI { a - std::f32::consts::TAU } else { a };
+        corners.push((a, rng.gen_range(0.5, 0.85)));
+    }
+    corners.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let longest = corners.iter().map(|c| c.1).fold(0.0, f32::max);
+    corners
+        .into_iter()
+        .map(|(a, r)| Vec2::from_angle(a + facing) * r / longest)
+        .collect()
+}
+
+// Nose, left and right corners of a ship triangle at `pos` facing `rot` degrees.
+pub fn ship_vertices(pos: Vec2, rot: f32) -> [Vec2; 3] {
+    let ang = rot.to_radians();
+    let dir_f = vec2(ang.sin(), -ang.cos());
+    let dir_l = vec2(-ang.cos(), -ang.sin());
+    let dir_r = vec2(ang.cos(), ang.sin());
+
+    let nose = pos + dir_f * (SHIP_HEIGHT * 0.8);
+    let back = pos + dir_f * -(SHIP_HEIGHT * 0.3);
+    let half_base = SHIP_BASE * 0.3;
+    [nose, back + dir_l * half_base, back + dir_r * half_base]
+}
+
+// The playfield is a torus: leaving one edge re-enters from the opposite one.
+fn wrap_around(v: &Vec2, size: Vec2) -> Vec2 {
+    vec2(v.x.rem_euclid(size.x), v.y.rem_euclid(size.y))
+}
+
+// Shortest vector from `from` to `to` across the wrapping edges.
+fn wrap_delta(from: Vec2, to: Vec2, size: Vec2) -> Vec2 {
+    let d = to - from;
+    vec2(
+        d.x - (d.x / size.x).round() * size.x,
+        d.y - (d.y / size.y).round() * size.y,
+    )
+}
+
+// `shape`, centred on `center`, moved to its wrapped copy nearest `anchor` so
+// that it can be tested against shapes around `anchor`.
+fn moved_near(shape: &[Vec2], center: Vec2, anchor: Vec2, size: Vec2) -> Vec<Vec2> {
+    let offset = anchor + wrap_delta(anchor, center, size) - center;
+    shape.iter().map(|p| *p + offset).collect()
+}
+
+pub fn speed_factor(level_multiplier: f64) -> f32 {
+    1.0 + (level_multiplier as f32) * 0.15
+}
+
+fn random_asteroid_color(rng: &mut Rng, colors: &[Color]) -> Color {
+    colors[rng.gen_range(0, colors.len())]
+}
+
+impl World {
+    // Starts a fresh run on level 1 inside a `width` x `height` playfield. The
+    // same seed always produces the same asteroid field.
+    pub fn new(seed: u64, palettes: &[ColorPalette], width: f32, height: f32) -> World {
+        let mut world = World {
+            ship: Ship::new(Vec2::ZERO),
+            projectiles: Vec::new(),
+            asteroids: Vec::new(),
+            saucers: Vec::new(),
+            power_ups: Vec::new(),
+            powers: [0.0; POWER_UPS.len()],
+            player_score: 0,
+            level_multiplier: 1.0,
+            lives: STARTING_LIVES,
+            next_extra_life: EXTRA_LIFE_EVERY,
+            extra_life_every: EXTRA_LIFE_EVERY,
+            game_over: false,
+            ship_lost_at: None,
+            palette: 0,
+            seed,
+            wave_started_at: 0.0,
+            asteroid_collisions: false,
+            events: Vec::new(),
+            rng: Rng::new(seed),
+            size: vec2(width, height),
+            time: 0.0,
+            last_shot: 0.0,
+            last_hyperspace: f64::MIN,
+            next_saucer_at: 0.0,
+            asteroid_colors: Vec::new(),
+            shots_fired: 0,
+        };
+        world.start_level(1.0, palettes);
+        world
+    }
+
+    // Moves on to the next level, keeping the score.
+    pub fn next_level(&mut self, palettes: &[ColorPalette]) {
+        self.start_level(self.level_multiplier + 1.0, palettes);
+    }
+
+    // Awards an extra life every `every` points from the start of the run,
+    // or never if it is 0.
+    pub fn set_extra_life_every(&mut self, every: u64) {
+        self.extra_life_every = every;
+        self.next_extra_life = every;
+    }
+
+    pub fn extra_life_every(&self) -> u64 {
+        self.extra_life_every
+    }
+
+    // Draws the level with `palettes[index]` from now on, recolouring the
+    // asteroids already in play to the colour at the same place in it.
+    pub fn set_palette(&mut self, index: usize, palettes: &[ColorPalette]) {
+        let colors = &palettes[index].asteroid_colors;
+        for a in self.asteroids.iter_mut() {
+            if let Some(i) = self.asteroid_colors.iter().position(|c| *c == a.color) {
+                a.color = colors[i % colors.len()];
+            }
+        }
+        self.palette = index;
+        self.asteroid_colors = colors.clone();
+    }
+
+    fn start_level(&mut self, level_multiplier: f64, palettes: &[ColorPalette]) {
+        let center = self.size / 2.0;
+        let min_dim = self.size.x.min(self.size.y);
+        let factor = speed_factor(level_multiplier);
+
+        self.level_multiplier = level_multiplier;
+        let palette =
+            pick_palette_for_level(level_multiplier, self.palette, palettes, &mut self.rng);
+        self.set_palette(palette, palettes);
+        self.ship = Ship::new(center);
+        self.projectiles.clear();
+        self.asteroids.clear();
+        self.saucers.clear();
+        self.power_ups.clear();
+        self.ship_lost_at = None;
+        self.last_shot = self.time;
+        self.wave_started_at = self.time;
+        self.schedule_saucer();
+        let size = self.size;
+        let asteroid_size = min_dim / 10.0;
+        let clearance = min_dim * SPAWN_CLEARANCE + asteroid_size;
+
+        for _ in 0..(5. + (level_multiplier * 2.)) as u32 {
+            let base_vel = vec2(self.rng.gen_range(-1., 1.), self.rng.gen_range(-1., 1.));
+            let norm = if base_vel.length() == 0. {
+                vec2(1.0, 0.0)
+            } else {
+                base_vel.normalize()
+            };
+            let mut pos = center
+                + vec2(self.rng.gen_range(-1., 1.), self.rng.gen_range(-1., 1.)).normalize()
+                    * min_dim
+                    / 2.0;
+            let mut away = wrap_delta(center, pos, size);
+            if away.length() < clearance {
+                away = away.normalize_or(Vec2::X) * clearance;
+                pos = wrap_around(&(center + away), size);
+            }
+            let mut vel = norm * factor * ASTEROID_SPEED;
+            // Closest approach to the ship over the grace period; if that
+            // enters the clear zone, flip the heading's inward component.
+            let t = (-away.dot(vel) / vel.length_squared()).clamp(0.0, SPAWN_HEADING_GRACE);
+            if (away + vel * t).length() < clearance {
+                let n = away.normalize();
+                vel -= 2.0 * vel.dot(n).min(0.0) * n;
+            }
+            let sides = self.rng.gen_range(3u8, 8u8);
+            self.asteroids.push(Asteroid {
+                pos,
+                vel,
+                prev_pos: pos,
+                rot: 0.0,
+                prev_rot: 0.0,
+                rot_speed: self.rng.gen_range(-1., 1.) * ASTEROID_ROT_SPEED,
+                size: asteroid_size,
+                sides,
+                shape: jagged_shape(&mut self.rng, sides),
+                immune_to: None,
+                collided: false,
+                color: random_asteroid_color(&mut self.rng, &self.asteroid_colors),
+            });
+        }
+    }
+
+    // Saucers turn up more often as the level's speed factor grows.
+    fn schedule_saucer(&mut self) {
+        let interval = SAUCER_INTERVAL / speed_factor(self.level_multiplier) as f64;
+        self.next_saucer_at = self.time + interval * self.rng.gen_range(0.75, 1.25);
+    }
+
+    fn update_saucers(&mut self, dt: f32) {
+        let now = self.time;
+        if self.saucers.is_empty() && now >= self.next_saucer_at {
+            let small_chance = (self.level_multiplier as f32 * 0.1).min(0.8);
+            let small = self.rng.gen_range(0.0, 1.0) < small_chance;
+            let factor = speed_factor(self.level_multiplier);
+            let saucer = Saucer::spawn(&mut self.rng, self.size, small, factor, now);
+            self.saucers.push(saucer);
+            self.schedule_saucer();
+        }
+
+        let target = self.ship_lost_at.is_none().then_some(self.ship.pos);
+        for s in self.saucers.iter_mut() {
+            s.update(dt, self.size.y);
+            let accuracy = self.level_multiplier as f32;
+            if let Some(dir) = s.fire(&mut self.rng, now, target, accuracy) {
+                let pos = s.pos + dir * s.radius();
+                self.projectiles
+                    .push(Projectile::bullet(pos, dir * BULLET_SPEED * 0.6, true, now));
+            }
+        }
+        let width = self.size.x;
+        self.saucers.retain(|s| !s.has_crossed(width));
+    }
+
+    // How far the current wave is through its spawn-in, from 0 to 1.
+    pub fn spawn_in(&self) -> f32 {
+        ((self.time - self.wave_started_at) as f32 / SPAWN_IN_TIME).min(1.0)
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.size
+    }
+
+    // Seconds since the run started.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    // Seconds left on `kind`, 0 if it is not running.
+    pub fn power(&self, kind: PowerUpKind) -> f32 {
+        self.powers[kind as usize]
+    }
+
+    pub fn is_cleared(&self) -> bool {
+        self.asteroids.is_empty()
+    }
+
+    // Blends the previous and current position of an object for rendering
+    // between two steps, taking the short way across a wrapped edge.
+    pub fn interpolate(&self, prev: Vec2, pos: Vec2, alpha: f32) -> Vec2 {
+        wrap_around(
+            &(prev + wrap_delta(prev, pos, self.size) * alpha),
+            self.size,
+        )
+    }
+
+    // Every position an object of `radius` at `pos` has to be drawn at so
+    // that it shows on both sides of any edge it straddles.
+    pub fn ghost_positions(&self, pos: Vec2, radius: f32) -> Vec<Vec2> {
+        let offsets = |p: f32, max: f32| {
+            let mut o = vec![0.0];
+            if p < radius {
+                o.push(max);
+            }
+            if p > max - radius {
+                o.push(-max);
+            }
+            o
+        };
+        let mut out = Vec::new();
+        for dx in offsets(pos.x, self.size.x) {
+            for dy in offsets(pos.y, self.size.y) {
+                out.push(pos + vec2(dx, dy));
+            }
+        }
+        out
+    }
+
+    // Advances the simulation by `dt` seconds. The frontend always passes
+    // `TICK` so that runs behave the same at any frame rate.
+    pub fn step(&mut self, input: &InputFrame, dt: f32) {
+        self.events.clear();
+        if self.game_over {
+            return;
+        }
+        self.time += dt as f64;
+        let now = self.time;
+
+        match self.ship_lost_at {
+            None => self.update_ship(input, dt),
+            Some(lost_at) => {
+                let center = self.size / 2.0;
+                let size = self.size;
+                let clear = self
+                    .asteroids
+                    .iter()
+                    .all(|a| wrap_delta(center, a.pos, size).length() > a.size + RESPAWN_CLEARANCE);
+                if (clear && now - lost_at > RESPAWN_DELAY) || now - lost_at > RESPAWN_TIMEOUT {
+                    self.ship = Ship::new(center);
+                    self.ship.invulnerable = INVULNERABLE_TIME;
+                    self.ship_lost_at = None;
+                }
+            }
+        }
+
+        for t in self.powers.iter_mut() {
+            *t = (*t - dt).max(0.0);
+        }
+        self.collect_power_ups();
+
+        let size = self.size;
+        for b in self.projectiles.iter_mut() {
+            if b.behaviour == Behaviour::Homing {
+                let nearest = self
+                    .asteroids
+                    .iter()
+                    .map(|a| a.pos)
+                    .chain(self.saucers.iter().map(|s| s.pos))
+                    .map(|p| wrap_delta(b.pos, p, size))
+                    .min_by(|x, y| x.length_squared().total_cmp(&y.length_squared()));
+                if let Some(d) = nearest {
+                    let turn = b.vel.perp_dot(d).atan2(b.vel.dot(d));
+                    let turn = turn.clamp(-MISSILE_TURN * dt, MISSILE_TURN * dt);
+                    b.vel = Vec2::from_angle(turn).rotate(b.vel);
+                }
+            }
+            b.prev_pos = b.pos;
+            b.pos += b.vel * dt;
+            b.pos = wrap_around(&b.pos, size);
+        }
+        let max_speed = (5.0 + (self.level_multiplier as f32) * 0.5) * ASTEROID_SPEED;
+        let spawning = self.spawn_in() < 1.0;
+        for a in self.asteroids.iter_mut() {
+            a.prev_pos = a.pos;
+            a.prev_rot = a.rot;
+            if spawning {
+                continue;
+            }
+            a.pos += a.vel * dt;
+            a.pos = wrap_around(&a.pos, self.size);
+            a.rot += a.rot_speed * dt;
+
+            if a.vel.length() > max_speed {
+                a.vel = a.vel.normalize() * max_speed;
+            }
+        }
+        if self.asteroid_collisions && !spawning {
+            self.collide_asteroids(max_speed);
+        }
+
+        self.update_saucers(dt);
+
+        self.projectiles
+            .retain(|b| b.shot_at + b.lifetime > now && !b.collided);
+        self.power_ups.retain(|p| p.expires_at > now);
+
+        // Every test below moves one shape to the wrapped copy nearest the
+        // other, so hits across an edge count. Projectiles are swept back
+        // along their velocity rather than from `prev_pos`, which may have
+        // wrapped.
+        let ship_pos = self.ship.pos;
+        let ship_shape = ship_vertices(ship_pos, self.ship.rot);
+        let sweep = |b: &Projectile, anchor: Vec2| {
+            moved_near(&[b.pos - b.vel * dt, b.pos], b.pos, anchor, size)
+        };
+        let ship_vulnerable = self.ship_lost_at.is_none()
+            && self.ship.invulnerable <= 0.0
+            && self.ship.warp <= 0.0
+            && self.power(PowerUpKind::Shield) <= 0.0;
+        let mut ship_hit = false;
+        let mut new_asts = Vec::new();
+        let wave_asteroid_size = size.x.min(size.y) / 10.0;
+        for a in self.asteroids.iter_mut() {
+            if spawning {
+                continue;
+            }
+            let outline = a.outline();
+            let away = wrap_delta(ship_pos, a.pos, size);
+            if ship_vulnerable && self.ship.shielded && away.length() < SHIELD_RADIUS + a.size {
+                self.ship.bounce(a, away, size);
+                self.events.push(WorldEvent::ShieldHit {
+                    pos: ship_pos + away.normalize_or_zero() * SHIELD_RADIUS,
+                    vel: a.vel,
+                });
+            } else if ship_vulnerable
+                && star_overlaps(
+                    a.pos,
+                    &outline,
+                    &moved_near(&ship_shape, ship_pos, a.pos, size),
+                )
+            {
+                ship_hit = true;
+            }
+            for s in self.saucers.iter_mut() {
+                if star_overlaps(a.pos, &outline, &moved_near(&s.hull(), s.pos, a.pos, size)) {
+                    s.destroyed = true;
+                }
+            }
+            for b in self.projectiles.iter_mut() {
+                if b.collided || b.id != 0 && a.immune_to == Some(b.id) {
+                    continue;
+                }
+                // Swept over the whole step so fast shots cannot tunnel.
+                let seg = sweep(b, a.pos);
+                if segment_hits_star(seg[0], seg[1], a.pos, &outline) {
+                    a.collided = true;
+                    b.hit();
+                    let side_count = a.sides;
+                    self.events.push(WorldEvent::AsteroidBroken {
+                        scale: a.size / wave_asteroid_size,
+                        outline: outline.clone(),
+                        vel: a.vel,
+                        color: a.color,
+                    });
+                    self.events.push(WorldEvent::Impact {
+                        pos: b.pos,
+                        vel: b.vel,
+                    });
+                    if !b.from_saucer {
+                        self.player_score += side_count as u64 * SCORE_MULTIPLIER as u64;
+                        if self.rng.gen_range(0.0, 1.0) < DROP_CHANCE {
+                            self.power_ups.push(PowerUp {
+                                pos: a.pos,
+                                kind: PowerUpKind::random(&mut self.rng),
+                                expires_at: now + PICKUP_LIFETIME,
+                            });
+                        }
+                    }
+                    // Each point of damage takes a side off the fragments.
+                    let sides = side_count.saturating_sub(b.damage);
+                    if sides >= 3 {
+                        for dir in [vec2(b.vel.y, -b.vel.x), vec2(-b.vel.y, b.vel.x)] {
+                            let dir = dir.normalize_or(Vec2::X);
+                            new_asts.push(Asteroid {
+                                pos: a.pos,
+                                vel: dir * speed_factor(self.level_multiplier) * ASTEROID_SPEED,
+                                prev_pos: a.pos,
+                                rot: a.rot,
+                                prev_rot: a.rot,
+                                rot_speed: self.rng.gen_range(-1., 1.) * ASTEROID_ROT_SPEED,
+                                size: a.size * 0.8f32.powi(b.damage as i32),
+                                sides,
+                                shape: fragment_shape(&mut self.rng, a, dir),
+                                immune_to: Some(b.id),
+                                collided: false,
+                                color: random_asteroid_color(&mut self.rng, &self.asteroid_colors),
+                            });
+                        }
+                    }
+                    break;
+                }
+            }
+        }
+
+        for s in self.saucers.iter_mut() {
+            let hull = s.hull();
+            if ship_vulnerable
+                && polygons_overlap(&moved_near(&ship_shape, ship_pos, s.pos, size), &hull)
+            {
+                s.destroyed = true;
+                if self.ship.shielded {
+                    self.ship.spend_shield();
+                    self.events.push(WorldEvent::ShieldHit {
+                        pos: s.pos,
+                        vel: Vec2::ZERO,
+                    });
+                } else {
+                    ship_hit = true;
+                }
+            }
+            for b in self.projectiles.iter_mut() {
+                if b.collided || b.from_saucer {
+                    continue;
+                }
+                let seg = sweep(b, s.pos);
+                if segment_hits_polygon(seg[0], seg[1], &hull) {
+                    b.hit();
+                    s.destroyed = true;
+                    self.events.push(WorldEvent::Impact {
+                        pos: b.pos,
+                        vel: b.vel,
+                    });
+                    self.player_score += s.points();
+                    break;
+                }
+            }
+        }
+        for b in self.projectiles.iter_mut() {
+            if !ship_vulnerable || !b.from_saucer || b.collided {
+                continue;
+            }
+            let seg = sweep(b, ship_pos);
+            if segment_hits_polygon(seg[0], seg[1], &ship_shape) {
+                b.collided = true;
+                if self.ship.shielded {
+                    self.ship.spend_shield();
+                    self.events.push(WorldEvent::ShieldHit {
+                        pos: b.pos,
+                        vel: -b.vel,
+                    });
+                } else {
+                    ship_hit = true;
+                    self.events.push(WorldEvent::Impact {
+                        pos: b.pos,
+                        vel: b.vel,
+                    });
+                }
+            }
+        }
+
+        self.asteroids.retain(|a| !a.collided);
+        self.asteroids.extend(new_asts);
+        for s in self.saucers.iter().filter(|s| s.destroyed) {
+            self.events.push(WorldEvent::SaucerDestroyed {
+                pos: s.pos,
+                radius: s.radius(),
+            });
+        }
+        self.saucers.retain(|s| !s.destroyed);
+
+        while self.extra_life_every > 0 && self.player_score >= self.next_extra_life {
+            self.lives += 1;
+            self.next_extra_life += self.extra_life_every;
+        }
+        if ship_hit {
+            self.lose_life();
+        }
+    }
+
+    fn lose_life(&mut self) {
+        self.events.push(WorldEvent::ShipDestroyed {
+            outline: ship_vertices(self.ship.pos, self.ship.rot),
+            vel: self.ship.vel,
+        });
+        self.lives = self.lives.saturating_sub(1);
+        self.powers = [0.0; POWER_UPS.len()];
+        if self.lives == 0 {
+            self.game_over = true;
+        } else {
+            self.ship_lost_at = Some(self.time);
+        }
+    }
+
+    // Bounces every touching pair of asteroids apart as an elastic collision
+    // along the line between their centres. Pairs already separating are
+    // left alone, which also lets freshly split fragments drift apart.
+    fn collide_asteroids(&mut self, max_speed: f32) {
+        let size = self.size;
+        let outlines: Vec<Vec<Vec2>> = self.asteroids.iter().map(|a| a.outline()).collect();
+        for i in 0..self.asteroids.len() {
+            for j in i + 1..self.asteroids.len() {
+                let (a, b) = (&self.asteroids[i], &self.asteroids[j]);
+                let d = wrap_delta(a.pos, b.pos, size);
+                if d.length() > a.size + b.size
+                    || !stars_overlap(
+                        a.pos,
+                        &outlines[i],
+                        a.pos + d,
+                        &moved_near(&outlines[j], b.pos, a.pos, size),
+                    )
+                {
+                    continue;
+                }
+                let n = d.normalize_or_zero();
+                let closing = (b.vel - a.vel).dot(n);
+                if closing >= 0.0 {
+                    continue;
+                }
+                let (ma, mb) = (a.mass(), b.mass());
+                let impulse = -2.0 * closing / (1.0 / ma + 1.0 / mb);
+                let a_vel = (a.vel - n * impulse / ma).clamp_length_max(max_speed);
+                let b_vel = (b.vel + n * impulse / mb).clamp_length_max(max_speed);
+                self.asteroids[i].vel = a_vel;
+                self.asteroids[j].vel = b_vel;
+            }
+        }
+    }
+
+    // Picks up anything the ship flies over.
+    fn collect_power_ups(&mut self) {
+        if self.ship_lost_at.is_some() || self.ship.warp > 0.0 {
+            return;
+        }
+        let (ship_pos, size) = (self.ship.pos, self.size);
+        let reach = PICKUP_RADIUS + SHIP_HEIGHT / 2.0;
+        for p in self.power_ups.iter_mut() {
+            if wrap_delta(ship_pos, p.pos, size).length() < reach {
+                self.powers[p.kind as usize] = p.kind.duration();
+                self.events.push(WorldEvent::PowerUp);
+                p.expires_at = f64::MIN;
+            }
+        }
+    }
+
+    fn update_ship(&mut self, input: &InputFrame, dt: f32) {
+        let now = self.time;
+        let powers = self.powers;
+        let ship = &mut self.ship;
+        ship.invulnerable = (ship.invulnerable - dt).max(0.0);
+        ship.prev_pos = ship.pos;
+        ship.prev_rot = ship.rot;
+
+        // Held down it drains; let go it recharges. It is down in hyperspace.
+        ship.shielded = input.shield && !ship.depleted && ship.warp <= 0.0;
+        ship.energy = if ship.shielded {
+            (ship.energy - SHIELD_DRAIN * dt).max(0.0)
+        } else {
+            (ship.energy + SHIELD_RECHARGE * dt).min(1.0)
+        };
+        if ship.energy <= 0.0 {
+            ship.depleted = true;
+        } else if ship.energy >= 1.0 {
+            ship.depleted = false;
+        }
+
+        if ship.warp > 0.0 {
+            ship.warp -= dt;
+            if ship.warp <= 0.0 {
+                ship.warp = 0.0;
+                if ship.warp_fails {
+                    self.lose_life();
+                }
+            }
+            return;
+        }
+        if input.hyperspace && now - self.last_hyperspace > HYPERSPACE_COOLDOWN {
+            let dest = vec2(
+                self.rng.gen_range(0.0, self.size.x),
+                self.rng.gen_range(0.0, self.size.y),
+            );
+            ship.warp_from = ship.pos;
+            ship.warp_fails = self.rng.gen_range(0.0, 1.0) < HYPERSPACE_FAILURE;
+            ship.warp = WARP_TIME;
+            ship.pos = wrap_around(&dest, self.size);
+            ship.prev_pos = ship.pos;
+            ship.vel = Vec2::ZERO;
+            self.last_hyperspace = now;
+            return;
+        }
+
+        let mut acc = -ship.vel * SHIP_DRAG;
+        ship.rot += input.rotate.clamp(-1.0, 1.0) * SHIP_ROT_SPEED * dt;
+        ship.thrusting = input.thrust > 0.0;
+        if ship.thrusting {
+            let ang = ship.rot.to_radians();
+            acc = vec2(ang.sin(), -ang.cos()) * SHIP_THRUST * input.thrust.min(1.0);
+        }
+        let fire_rate = if powers[PowerUpKind::Rapid as usize] > 0.0 {
+            FIRE_RATE / RAPID_FIRE
+        } else {
+            FIRE_RATE
+        };
+        if input.fire && now - self.last_shot > fire_rate {
+            let spread: &[f32] = if powers[PowerUpKind::Spread as usize] > 0.0 {
+                &[-SPREAD_ANGLE, 0.0, SPREAD_ANGLE]
+            } else {
+                &[0.0]
+            };
+            for offset in spread {
+                let ang = (ship.rot + offset).to_radians();
+                let dir = vec2(ang.sin(), -ang.cos());
+                let pos = ship.pos + dir * (SHIP_HEIGHT / 2.0);
+                self.shots_fired += 1;
+                self.projectiles.push(Projectile {
+                    id: self.shots_fired,
+                    ..Projectile::armed(pos, dir, &powers, now)
+                });
+            }
+            self.last_shot = now;
+            self.events.push(WorldEvent::Fire);
+        }
+
+        ship.vel += acc * dt;
+        if ship.vel.length() > SHIP_MAX_SPEED {
+            ship.vel = ship.vel.normalize() * SHIP_MAX_SPEED;
+        }
+        ship.pos += ship.vel * dt;
+        ship.pos = wrap_around(&ship.pos, self.size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use macroquad::color::{BLACK, WHITE};
+
+    fn palettes() -> Vec<ColorPalette> {
+        vec![ColorPalette {
+            name: "Test".to_string(),
+            background: BLACK,
+            ship: WHITE,
+            asteroid_colors: vec![WHITE],
+            next: None,
+        }]
+    }
+
+    // A world past the spawn-in freeze, so everything moves and collides.
+    fn world(seed: u64) -> World {
+        let mut world = World::new(seed, &palettes(), FIELD_WIDTH, FIELD_HEIGHT);
+        world.wave_started_at = -1.0;
+        world
+    }
+
+    #[test]
+    fn ship_hit_by_asteroid_loses_a_life() {
+        let mut world = world(1);
+        let lives = world.lives;
+        world.asteroids.truncate(1);
+        world.asteroids[0].pos = world.ship.pos;
+        world.asteroids[0].vel = Vec2::ZERO;
+        world.step(&InputFrame::default(), TICK);
+        assert_eq!(world.lives, lives - 1);
+        assert!(world.ship_lost_at.is_some());
+    }
+
+    #[test]
+    fn bullet_splits_asteroid_into_two_smaller_ones() {
+        let mut world = world(2);
+        world.asteroids.truncate(1);
+        let a = &mut world.asteroids[0];
+        a.pos = vec2(100.0, 100.0);
+        a.vel = Vec2::ZERO;
+        a.sides = 5;
+        let (size, pos) = (a.size, a.pos);
+        let shot = Projectile::bullet(pos - vec2(2.0, 0.0), vec2(BULLET_SPEED, 0.0), false, 0.0);
+        world.projectiles.push(shot);
+        world.step(&InputFrame::default(), TICK);
+
+        assert_eq!(world.asteroids.len(), 2);
+        for a in &world.asteroids {
+            assert_eq!(a.sides, 4);
+            assert!(a.size < size);
+        }
+        assert_eq!(world.player_score, 5 * SCORE_MULTIPLIER as u64);
+        assert!(world.projectiles.iter().all(|p| p.collided));
+    }
+
+    #[test]
+    fn emptied_shield_stays_down_until_recharged() {
+        let mut world = world(4);
+        world.asteroids.truncate(1);
+        let shield = InputFrame {
+            shield: true,
+            ..Default::default()
+        };
+        // Drops the asteroid on the ship, heading into it.
+        let touch = |world: &mut World| {
+            let a = &mut world.asteroids[0];
+            a.pos = world.ship.pos + vec2(1.0, 0.0);
+            a.vel = vec2(-ASTEROID_SPEED, 0.0);
+        };
+
+        world.step(&shield, TICK);
+        touch(&mut world);
+        world.step(&shield, TICK);
+        assert!(world.ship.depleted);
+        assert_eq!(world.ship.energy, 0.0);
+        world.asteroids[0].pos = world.ship.pos + vec2(300.0, 0.0);
+        world.asteroids[0].vel = Vec2::ZERO;
+
+        let recharge = (1.0 / (SHIELD_RECHARGE * TICK)).ceil() as usize;
+        for _ in 0..recharge / 2 {
+            world.step(&shield, TICK);
+            assert!(!world.ship.shielded);
+        }
+        let lives = world.lives;
+        touch(&mut world);
+        world.step(&shield, TICK);
+        assert_eq!(world.lives, lives - 1);
+
+        let mut world = World {
+            ship: Ship::new(world.ship.pos),
+            ..world
+        };
+        world.ship.energy = 0.0;
+        world.ship.depleted = true;
+        for _ in 0..recharge + 1 {
+            world.step(&InputFrame::default(), TICK);
+        }
+        world.step(&shield, TICK);
+        assert!(world.ship.shielded);
+    }
+
+    #[test]
+    fn positions_wrap_at_the_field_edge() {
+        let mut world = world(3);
+        world.asteroids.truncate(1);
+        world.asteroids[0].pos = vec2(FIELD_WIDTH - 1.0, 50.0);
+        world.asteroids[0].vel = vec2(300.0, 0.0);
+        world.ship.pos = vec2(FIELD_WIDTH / 2.0, 1.0);
+        world.ship.vel = vec2(0.0, -120.0);
+        world.step(&InputFrame::default(), TICK);
+
+        let a = world.asteroids[0].pos;
+        assert!(a.x >= 0.0 && a.x < 10.0, "asteroid at {a}");
+        let s = world.ship.pos;
+        assert!(
+            s.y > FIELD_HEIGHT - 10.0 && s.y < FIELD_HEIGHT,
+            "ship at {s}"
+        );
+    }
+
+    #[test]
+    fn same_seed_and_input_give_the_same_run() {
+        let run = |seed| {
+            let mut world = World::new(seed, &palettes(), FIELD_WIDTH, FIELD_HEIGHT);
+            for tick in 0..1200 {
+                let input = InputFrame {
+                    rotate: if tick % 200 < 100 { 1.0 } else { -0.5 },
+                    thrust: if tick % 90 < 30 { 1.0 } else { 0.0 },
+                    fire: tick % 7 == 0,
+                    hyperspace: tick == 600,
+                    shield: tick % 300 > 280,
+                };
+                world.step(&input, TICK);
+                if world.is_cleared() {
+                    world.next_level(&palettes());
+                }
+            }
+            world
+        };
+        let (a, b) = (run(7), run(7));
+        assert_eq!(a.player_score, b.player_score);
+        assert_eq!(a.lives, b.lives);
+        assert_eq!(a.ship.pos, b.ship.pos);
+        assert_eq!(a.ship.rot, b.ship.rot);
+        assert_eq!(a.asteroids.len(), b.asteroids.len());
+        for (x, y) in a.asteroids.iter().zip(&b.asteroids) {
+            assert_eq!(x.pos, y.pos);
+            assert_eq!(x.shape, y.shape);
+        }
+        assert_eq!(a.projectiles.len(), b.projectiles.len());
+        assert_eq!(a.saucers.len(), b.saucers.len());
+    }
+}